@@ -1,4 +1,6 @@
 use anyhow::Result;
+use lazy_static::lazy_static;
+use log::error;
 use serde::Serialize;
 use sqlx::Row;
 use tauri::{async_runtime::Mutex, AppHandle, Emitter, State};
@@ -10,6 +12,10 @@ use crate::{
     AppState,
 };
 
+lazy_static! {
+    static ref REFRESH_LOCK: Mutex<()> = Mutex::new(());
+}
+
 #[derive(Serialize)]
 pub struct Feed {
     twitch: Option<Vec<LiveNow>>,
@@ -25,7 +31,8 @@ pub async fn get_feed(
     let feeds_db = state.feeds_db.as_ref().unwrap();
 
     if platform == Platform::Twitch {
-        let query = "SELECT username, started_at FROM twitch";
+        let query =
+            "SELECT username, started_at, title, game, viewers, tags, thumbnail FROM twitch";
 
         let rows = match sqlx::query(query).fetch_all(feeds_db).await {
             Ok(rows) => rows,
@@ -37,9 +44,22 @@ pub async fn get_feed(
         let mut feed: Vec<LiveNow> = Vec::new();
 
         for row in rows {
+            // Rows from before the metadata was stored have these empty
+            let title: Option<String> = row.try_get("title").map_err(|e| e.to_string())?;
+            let viewers: Option<i64> = row.try_get("viewers").map_err(|e| e.to_string())?;
+            let tags: Option<String> = row.try_get("tags").map_err(|e| e.to_string())?;
+            let thumbnail: Option<String> = row.try_get("thumbnail").map_err(|e| e.to_string())?;
+
             let live_now = LiveNow {
                 username: row.try_get("username").map_err(|e| e.to_string())?,
                 started_at: row.try_get("started_at").map_err(|e| e.to_string())?,
+                title: title.unwrap_or_default(),
+                game: row.try_get("game").map_err(|e| e.to_string())?,
+                viewers: viewers.unwrap_or_default(),
+                tags: tags
+                    .and_then(|tags| serde_json::from_str(&tags).ok())
+                    .unwrap_or_default(),
+                thumbnail: thumbnail.unwrap_or_default(),
             };
 
             feed.push(live_now);
@@ -90,9 +110,20 @@ pub async fn refresh_feed(
     state: State<'_, Mutex<AppState>>,
     platform: Platform,
 ) -> Result<(), String> {
-    let state = state.lock().await;
-    let users_db = state.users_db.as_ref().unwrap();
-    let feeds_db = state.feeds_db.as_ref().unwrap();
+    // Only one refresh runs at a time, the app state is not kept locked while fetching
+    // so other commands don't have to wait for the refresh to finish
+    let _refreshing = REFRESH_LOCK.lock().await;
+
+    let (users_db, feeds_db) = {
+        let state = state.lock().await;
+        (
+            state.users_db.clone().unwrap(),
+            state.feeds_db.clone().unwrap(),
+        )
+    };
+
+    let users_db = &users_db;
+    let feeds_db = &feeds_db;
 
     if platform == Platform::Twitch {
         let query = "SELECT username FROM twitch";
@@ -111,34 +142,119 @@ pub async fn refresh_feed(
             usernames.push(username);
         }
 
-        let live_now = match twitch::stream::fetch_live_now(usernames).await {
-            Ok(live_now) => live_now,
+        let (live_now, failed) = match twitch::stream::fetch_live_now(usernames.clone()).await {
+            Ok(result) => result,
             Err(err) => {
                 return Err(format!("Failed to fetch live now: {err}"));
             }
         };
 
-        let query = "DELETE FROM twitch";
+        let query = "SELECT username FROM twitch";
 
-        sqlx::query(query)
-            .execute(feeds_db)
-            .await
-            .map_err(|e| e.to_string())?;
+        let rows = match sqlx::query(query).fetch_all(feeds_db).await {
+            Ok(rows) => rows,
+            Err(err) => {
+                return Err(format!("Failed to fetch feed: {err}"));
+            }
+        };
+
+        let mut previously_live: Vec<String> = Vec::new();
+
+        for row in rows {
+            let username = row.try_get("username").map_err(|e| e.to_string())?;
+            previously_live.push(username);
+        }
+
+        let query = "SELECT username FROM twitch_checked";
 
-        for (username, live) in live_now {
-            let query = "INSERT INTO twitch (username, started_at) VALUES (?, ?)";
+        let rows = match sqlx::query(query).fetch_all(feeds_db).await {
+            Ok(rows) => rows,
+            Err(err) => {
+                return Err(format!("Failed to fetch checked users: {err}"));
+            }
+        };
+
+        let mut previously_checked: Vec<String> = Vec::new();
+
+        for row in rows {
+            let username = row.try_get("username").map_err(|e| e.to_string())?;
+            previously_checked.push(username);
+        }
+
+        // Users checked for the first time, like new follows, may have been live for hours
+        let went_live: Vec<String> = live_now
+            .keys()
+            .filter(|username| {
+                previously_checked.contains(username) && !previously_live.contains(username)
+            })
+            .cloned()
+            .collect();
+
+        // Users that couldn't be checked keep their previous state
+        for username in usernames.iter().filter(|u| !failed.contains(u)) {
+            let query = "DELETE FROM twitch WHERE username = ?";
+
+            sqlx::query(query)
+                .bind(username)
+                .execute(feeds_db)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let query = "INSERT OR IGNORE INTO twitch_checked (username) VALUES (?)";
+
+            sqlx::query(query)
+                .bind(username)
+                .execute(feeds_db)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        for (username, live) in &live_now {
+            let query = "INSERT INTO twitch (username, started_at, title, game, viewers, tags, thumbnail) VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+            let tags = serde_json::to_string(&live.tags).map_err(|e| e.to_string())?;
 
             sqlx::query(query)
-                .bind(&username)
+                .bind(username)
                 .bind(&live.started_at)
+                .bind(&live.title)
+                .bind(&live.game)
+                .bind(live.viewers)
+                .bind(&tags)
+                .bind(&live.thumbnail)
                 .execute(feeds_db)
                 .await
                 .map_err(|e| e.to_string())?;
         }
 
+        let offline: Vec<&String> = usernames
+            .iter()
+            .filter(|u| !failed.contains(u) && !live_now.contains_key(*u))
+            .collect();
+
+        if let Err(err) = twitch::session::update_sessions(feeds_db, &live_now, &offline).await {
+            error!("Failed to update stream sessions: {err}");
+        }
+
         if let Err(err) = app_handle.emit("updated_streams", &platform) {
             return Err(format!("Error emitting 'updated_streams' event: {err}"));
         }
+
+        if let Err(err) = twitch::notification::emit_went_live(
+            &app_handle,
+            users_db,
+            went_live.iter().filter_map(|u| live_now.get(u)).collect(),
+        )
+        .await
+        {
+            error!("Failed to emit went live events: {err}");
+        }
+
+        if let Err(err) =
+            twitch::auto_record::start_auto_recordings(&app_handle, users_db, &went_live).await
+        {
+            error!("Failed to start auto recordings: {err}");
+        }
     }
 
     if platform == Platform::YouTube {
@@ -3,12 +3,14 @@ use log::{error, LevelFilter};
 use sqlx::SqlitePool;
 use tauri::{
     async_runtime::{self, Mutex},
-    Manager,
+    Manager, WindowEvent,
 };
 use tauri_plugin_deep_link::DeepLinkExt;
 
 mod feed;
 mod migration;
+mod scheduler;
+mod settings;
 mod twitch;
 mod user;
 mod util;
@@ -50,6 +52,7 @@ pub fn run() {
                 .build(),
         )
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_deep_link::init())
@@ -115,19 +118,61 @@ pub fn run() {
                 })))
             })?;
 
+            let app_handle = app.handle().clone();
+            async_runtime::spawn(async move {
+                if let Err(err) = twitch::server::start(app_handle).await {
+                    error!("Failed to start HLS server: {err}");
+                }
+            });
+
+            scheduler::start(app.handle().clone());
+
             Ok(())
         });
 
     builder
+        .on_window_event(|window, event| {
+            if let WindowEvent::Destroyed = event {
+                let window_label = window.label().to_string();
+                async_runtime::spawn(async move {
+                    twitch::proxy::remove_window_state(&window_label).await;
+                });
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             user::get_users,
             user::add_user,
             user::remove_user,
             feed::get_feed,
             feed::refresh_feed,
+            scheduler::get_refresh_settings,
+            scheduler::set_refresh_settings,
             window::open_new_window,
             twitch::stream::fetch_stream_playback,
-            twitch::proxy::proxy_stream,
+            twitch::server::stream_proxy_url,
+            twitch::server::vod_proxy_url,
+            twitch::vod::fetch_vod_playback,
+            twitch::clip::fetch_clip,
+            twitch::stats::get_ad_breaks,
+            twitch::quality::get_stream_qualities,
+            twitch::quality::get_preferred_quality,
+            twitch::quality::set_preferred_quality,
+            twitch::recorder::start_recording,
+            twitch::recorder::stop_recording,
+            twitch::recorder::get_recordings,
+            twitch::auto_record::get_auto_record_users,
+            twitch::auto_record::set_auto_record,
+            twitch::auto_record::get_recording_settings,
+            twitch::auto_record::set_recording_settings,
+            twitch::notification::get_notification_settings,
+            twitch::notification::set_notifications,
+            twitch::session::get_stream_sessions,
+            twitch::directory::get_top_categories,
+            twitch::directory::get_category_streams,
+            twitch::directory::get_top_streams,
+            twitch::search::search_channels,
+            twitch::user::import_follows,
+            twitch::profile::fetch_channel_profile,
             twitch::chat::join_chat,
             youtube::main::import_subscriptions,
         ])
@@ -1,10 +1,11 @@
 use tauri_plugin_sql::{Migration, MigrationKind};
 
 pub fn users_migrations() -> Vec<Migration> {
-    vec![Migration {
-        version: 1,
-        description: "create_users_table",
-        sql: r"
+    vec![
+        Migration {
+            version: 1,
+            description: "create_users_table",
+            sql: r"
                 CREATE TABLE IF NOT EXISTS twitch (
                     id TEXT,
                     username TEXT NOT NULL PRIMARY KEY,
@@ -17,15 +18,67 @@ pub fn users_migrations() -> Vec<Migration> {
                     avatar BLOB
                 );
             ",
-        kind: MigrationKind::Up,
-    }]
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 2,
+            description: "create_auto_record_and_settings_tables",
+            sql: r"
+                CREATE TABLE IF NOT EXISTS twitch_auto_record (
+                    username TEXT NOT NULL PRIMARY KEY,
+                    quality TEXT
+                );
+
+                CREATE TABLE IF NOT EXISTS settings (
+                    key TEXT NOT NULL PRIMARY KEY,
+                    value TEXT NOT NULL
+                );
+            ",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 3,
+            description: "create_quality_table",
+            sql: r"
+                CREATE TABLE IF NOT EXISTS twitch_quality (
+                    username TEXT NOT NULL PRIMARY KEY,
+                    quality TEXT NOT NULL
+                );
+            ",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 4,
+            description: "create_notifications_table",
+            sql: r"
+                CREATE TABLE IF NOT EXISTS twitch_notifications (
+                    username TEXT NOT NULL PRIMARY KEY,
+                    enabled INTEGER NOT NULL
+                );
+            ",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 5,
+            description: "create_recordings_table",
+            sql: r"
+                CREATE TABLE IF NOT EXISTS recordings (
+                    path TEXT NOT NULL PRIMARY KEY,
+                    username TEXT NOT NULL,
+                    started_at INTEGER NOT NULL
+                );
+            ",
+            kind: MigrationKind::Up,
+        },
+    ]
 }
 
 pub fn feeds_migrations() -> Vec<Migration> {
-    vec![Migration {
-        version: 1,
-        description: "create_feeds_table",
-        sql: r"
+    vec![
+        Migration {
+            version: 1,
+            description: "create_feeds_table",
+            sql: r"
                 CREATE TABLE IF NOT EXISTS twitch (
                     username TEXT NOT NULL PRIMARY KEY,
                     started_at TEXT
@@ -39,8 +92,69 @@ pub fn feeds_migrations() -> Vec<Migration> {
                     view_count TEXT
                 );
             ",
-        kind: MigrationKind::Up,
-    }]
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 2,
+            description: "create_ad_breaks_table",
+            sql: r"
+                CREATE TABLE IF NOT EXISTS ad_breaks (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    username TEXT NOT NULL,
+                    started_at INTEGER NOT NULL,
+                    ended_at INTEGER NOT NULL,
+                    duration INTEGER NOT NULL,
+                    backup_succeeded INTEGER NOT NULL
+                );
+            ",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 3,
+            description: "add_stream_metadata_columns",
+            sql: r"
+                ALTER TABLE twitch ADD COLUMN title TEXT;
+                ALTER TABLE twitch ADD COLUMN game TEXT;
+                ALTER TABLE twitch ADD COLUMN viewers INTEGER;
+                ALTER TABLE twitch ADD COLUMN tags TEXT;
+                ALTER TABLE twitch ADD COLUMN thumbnail TEXT;
+            ",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 4,
+            description: "create_stream_sessions_tables",
+            sql: r"
+                CREATE TABLE IF NOT EXISTS stream_sessions (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    username TEXT NOT NULL,
+                    started_at INTEGER NOT NULL,
+                    last_seen_at INTEGER NOT NULL,
+                    ended_at INTEGER,
+                    UNIQUE (username, started_at)
+                );
+
+                CREATE TABLE IF NOT EXISTS stream_session_changes (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    session_id INTEGER NOT NULL REFERENCES stream_sessions (id) ON DELETE CASCADE,
+                    changed_at INTEGER NOT NULL,
+                    title TEXT,
+                    game TEXT
+                );
+            ",
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 5,
+            description: "create_twitch_checked_table",
+            sql: r"
+                CREATE TABLE IF NOT EXISTS twitch_checked (
+                    username TEXT PRIMARY KEY
+                );
+            ",
+            kind: MigrationKind::Up,
+        },
+    ]
 }
 
 pub fn emotes_migrations() -> Vec<Migration> {
@@ -0,0 +1,248 @@
+use std::{pin::pin, time::Duration};
+
+use anyhow::Result;
+use futures_util::future::{self, Either};
+use lazy_static::lazy_static;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use tauri::{
+    async_runtime::{self, Mutex},
+    AppHandle, Manager, State,
+};
+use tokio::{net::TcpStream, sync::Notify};
+
+use crate::{feed, settings, user::Platform, util, AppState};
+
+const TWITCH_INTERVAL_SETTING: &str = "twitch_refresh_interval";
+const YOUTUBE_INTERVAL_SETTING: &str = "youtube_refresh_interval";
+const PAUSE_ON_BATTERY_SETTING: &str = "refresh_pause_on_battery";
+
+/// Default intervals in minutes.
+const DEFAULT_TWITCH_INTERVAL: u64 = 2;
+const DEFAULT_YOUTUBE_INTERVAL: u64 = 30;
+
+/// How often a disabled or paused refresh checks again if it should run.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    /// Wakes the refresh loops when the settings are saved, so they don't wait for the old interval to end.
+    static ref SETTINGS_CHANGED: Notify = Notify::new();
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshSettings {
+    /// Minutes between refreshes, `0` disables them.
+    pub twitch_interval: u64,
+    pub youtube_interval: u64,
+    /// Skips refreshes while running on battery, only supported on Linux, ignored on other platforms.
+    pub pause_on_battery: bool,
+}
+
+#[tauri::command]
+pub async fn get_refresh_settings(
+    state: State<'_, Mutex<AppState>>,
+) -> Result<RefreshSettings, String> {
+    let state = state.lock().await;
+    let users_db = state.users_db.as_ref().unwrap();
+
+    query_refresh_settings(users_db)
+        .await
+        .map_err(|err| format!("Failed to fetch refresh settings: {err}"))
+}
+
+#[tauri::command]
+pub async fn set_refresh_settings(
+    state: State<'_, Mutex<AppState>>,
+    refresh_settings: RefreshSettings,
+) -> Result<(), String> {
+    let state = state.lock().await;
+    let users_db = state.users_db.as_ref().unwrap();
+
+    let values = [
+        (
+            TWITCH_INTERVAL_SETTING,
+            refresh_settings.twitch_interval.to_string(),
+        ),
+        (
+            YOUTUBE_INTERVAL_SETTING,
+            refresh_settings.youtube_interval.to_string(),
+        ),
+        (
+            PAUSE_ON_BATTERY_SETTING,
+            refresh_settings.pause_on_battery.to_string(),
+        ),
+    ];
+
+    for (key, value) in values {
+        if let Err(err) = settings::set_setting(users_db, key, Some(&value)).await {
+            return Err(format!("Failed to save setting '{key}': {err}"));
+        }
+    }
+
+    SETTINGS_CHANGED.notify_waiters();
+
+    Ok(())
+}
+
+/// Starts refreshing the feeds of every platform in the background.
+///
+/// Refreshes emit `updated_streams` and `updated_videos` the same way as when pressing the refresh button.
+pub fn start(app_handle: AppHandle) {
+    for platform in [Platform::Twitch, Platform::YouTube] {
+        let app_handle = app_handle.clone();
+
+        async_runtime::spawn(async move {
+            run(app_handle, platform).await;
+        });
+    }
+}
+
+async fn run(app_handle: AppHandle, platform: Platform) {
+    loop {
+        let refresh_settings = {
+            let state = app_handle.state::<Mutex<AppState>>();
+            let users_db = state.lock().await.users_db.clone().unwrap();
+
+            match query_refresh_settings(&users_db).await {
+                Ok(refresh_settings) => refresh_settings,
+                Err(err) => {
+                    error!("Failed to fetch refresh settings: {err}");
+                    tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+                    continue;
+                }
+            }
+        };
+
+        let interval = match platform {
+            Platform::Twitch => refresh_settings.twitch_interval,
+            Platform::YouTube => refresh_settings.youtube_interval,
+        };
+
+        if interval == 0 {
+            sleep_or_settings_changed(IDLE_CHECK_INTERVAL).await;
+            continue;
+        }
+
+        let interval = Duration::from_secs(interval.saturating_mul(60));
+
+        if sleep_or_settings_changed(with_jitter(interval)).await {
+            continue;
+        }
+
+        if refresh_settings.pause_on_battery && on_battery() {
+            info!("Running on battery, skipping {platform:?} feed refresh");
+            continue;
+        }
+
+        if !is_online(platform).await {
+            info!("Offline, skipping {platform:?} feed refresh");
+            continue;
+        }
+
+        let state = app_handle.state::<Mutex<AppState>>();
+
+        if let Err(err) = feed::refresh_feed(app_handle.clone(), state, platform).await {
+            error!("Failed to refresh {platform:?} feed: {err}");
+        }
+    }
+}
+
+async fn query_refresh_settings(db: &Pool<Sqlite>) -> Result<RefreshSettings> {
+    let twitch_interval = settings::get_setting(db, TWITCH_INTERVAL_SETTING)
+        .await?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_TWITCH_INTERVAL);
+
+    let youtube_interval = settings::get_setting(db, YOUTUBE_INTERVAL_SETTING)
+        .await?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_YOUTUBE_INTERVAL);
+
+    let pause_on_battery = settings::get_setting(db, PAUSE_ON_BATTERY_SETTING)
+        .await?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(true);
+
+    Ok(RefreshSettings {
+        twitch_interval,
+        youtube_interval,
+        pause_on_battery,
+    })
+}
+
+/// Sleeps for `duration`, returns `true` if it was woken up early because the settings changed.
+async fn sleep_or_settings_changed(duration: Duration) -> bool {
+    let sleep = pin!(tokio::time::sleep(duration));
+    let changed = pin!(SETTINGS_CHANGED.notified());
+
+    matches!(future::select(sleep, changed).await, Either::Right(_))
+}
+
+/// Adds or removes up to 10% of the interval, so refreshes of both platforms don't keep happening at the same time.
+fn with_jitter(interval: Duration) -> Duration {
+    // Capped so twice the jitter still fits in the random number range
+    let jitter = u32::try_from(interval.as_millis() / 10)
+        .unwrap_or(u32::MAX)
+        .min(u32::MAX / 2);
+
+    if jitter == 0 {
+        return interval;
+    }
+
+    let offset = util::random_number(0, jitter * 2);
+
+    interval
+        .saturating_add(Duration::from_millis(offset.into()))
+        .saturating_sub(Duration::from_millis(jitter.into()))
+}
+
+/// Checks if the platform's API can be reached.
+async fn is_online(platform: Platform) -> bool {
+    let host = match platform {
+        Platform::Twitch => "gql.twitch.tv:443",
+        Platform::YouTube => "www.youtube.com:443",
+    };
+
+    matches!(
+        tokio::time::timeout(Duration::from_secs(5), TcpStream::connect(host)).await,
+        Ok(Ok(_))
+    )
+}
+
+/// Only supported on Linux, where the power supplies are listed in `/sys/class/power_supply`,
+/// on Windows and macOS this always returns `false`, so refreshes are never paused.
+fn on_battery() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+            return false;
+        };
+
+        let mut has_mains = false;
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+            if kind.trim() != "Mains" {
+                continue;
+            }
+
+            has_mains = true;
+
+            let online = std::fs::read_to_string(path.join("online")).unwrap_or_default();
+            if online.trim() == "1" {
+                return false;
+            }
+        }
+
+        has_mains
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
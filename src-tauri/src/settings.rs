@@ -0,0 +1,38 @@
+use anyhow::Result;
+use sqlx::{Pool, Row, Sqlite};
+
+/// Returns the value of a setting from the `settings` table in the users database.
+pub async fn get_setting(db: &Pool<Sqlite>, key: &str) -> Result<Option<String>> {
+    let query = "SELECT value FROM settings WHERE key = ?";
+
+    let row = sqlx::query(query).bind(key).fetch_optional(db).await?;
+
+    match row {
+        Some(row) => Ok(row.try_get("value")?),
+        None => Ok(None),
+    }
+}
+
+/// Saves a setting, `None` removes it.
+pub async fn set_setting(db: &Pool<Sqlite>, key: &str, value: Option<&str>) -> Result<()> {
+    let Some(value) = value else {
+        sqlx::query("DELETE FROM settings WHERE key = ?")
+            .bind(key)
+            .execute(db)
+            .await?;
+
+        return Ok(());
+    };
+
+    let query =
+        "INSERT INTO settings (key, value) VALUES (?, ?) ON CONFLICT (key) DO UPDATE SET value = ?";
+
+    sqlx::query(query)
+        .bind(key)
+        .bind(value)
+        .bind(value)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
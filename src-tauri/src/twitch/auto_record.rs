@@ -0,0 +1,321 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+use tauri::{async_runtime, async_runtime::Mutex, AppHandle, Manager, State};
+
+use crate::{settings, AppState};
+
+use super::recorder;
+
+const DIRECTORY_SETTING: &str = "recordings_directory";
+const MAX_DISK_USAGE_SETTING: &str = "recordings_max_disk_usage";
+const RETENTION_DAYS_SETTING: &str = "recordings_retention_days";
+
+#[derive(Serialize)]
+pub struct AutoRecordUser {
+    pub username: String,
+    pub quality: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingSettings {
+    /// Defaults to the `rt` folder in the user's videos directory.
+    pub directory: Option<String>,
+    /// Maximum size in bytes of the recordings made by the app in the directory, other files don't count.
+    pub max_disk_usage: Option<u64>,
+    /// Recordings older than this are removed.
+    pub retention_days: Option<u64>,
+}
+
+#[tauri::command]
+pub async fn get_auto_record_users(
+    state: State<'_, Mutex<AppState>>,
+) -> Result<Vec<AutoRecordUser>, String> {
+    let state = state.lock().await;
+    let users_db = state.users_db.as_ref().unwrap();
+
+    query_auto_record_users(users_db)
+        .await
+        .map_err(|err| format!("Failed to fetch auto record users: {err}"))
+}
+
+/// Enables or disables recording a user's stream when they go live.
+#[tauri::command]
+pub async fn set_auto_record(
+    state: State<'_, Mutex<AppState>>,
+    username: String,
+    enabled: bool,
+    quality: Option<String>,
+) -> Result<(), String> {
+    let state = state.lock().await;
+    let users_db = state.users_db.as_ref().unwrap();
+
+    if !enabled {
+        let query = "DELETE FROM twitch_auto_record WHERE username = ?";
+
+        sqlx::query(query)
+            .bind(&username)
+            .execute(users_db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        return Ok(());
+    }
+
+    let query = "INSERT INTO twitch_auto_record (username, quality) VALUES (?, ?) ON CONFLICT (username) DO UPDATE SET quality = ?";
+
+    sqlx::query(query)
+        .bind(&username)
+        .bind(&quality)
+        .bind(&quality)
+        .execute(users_db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_recording_settings(
+    state: State<'_, Mutex<AppState>>,
+) -> Result<RecordingSettings, String> {
+    let state = state.lock().await;
+    let users_db = state.users_db.as_ref().unwrap();
+
+    query_recording_settings(users_db)
+        .await
+        .map_err(|err| format!("Failed to fetch recording settings: {err}"))
+}
+
+#[tauri::command]
+pub async fn set_recording_settings(
+    state: State<'_, Mutex<AppState>>,
+    recording_settings: RecordingSettings,
+) -> Result<(), String> {
+    let state = state.lock().await;
+    let users_db = state.users_db.as_ref().unwrap();
+
+    let values = [
+        (DIRECTORY_SETTING, recording_settings.directory),
+        (
+            MAX_DISK_USAGE_SETTING,
+            recording_settings.max_disk_usage.map(|v| v.to_string()),
+        ),
+        (
+            RETENTION_DAYS_SETTING,
+            recording_settings.retention_days.map(|v| v.to_string()),
+        ),
+    ];
+
+    for (key, value) in values {
+        if let Err(err) = settings::set_setting(users_db, key, value.as_deref()).await {
+            return Err(format!("Failed to save setting '{key}': {err}"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Starts recording the users that just went live and have auto record enabled.
+///
+/// Old recordings are cleaned up first following the retention rules, whatever disk space is left
+/// under the limit is shared by the new recordings.
+pub async fn start_auto_recordings(
+    app_handle: &AppHandle,
+    users_db: &Pool<Sqlite>,
+    went_live: &[String],
+) -> Result<()> {
+    let users: Vec<AutoRecordUser> = query_auto_record_users(users_db)
+        .await?
+        .into_iter()
+        .filter(|user| went_live.contains(&user.username))
+        .collect();
+
+    if users.is_empty() {
+        return Ok(());
+    }
+
+    let recording_settings = query_recording_settings(users_db).await?;
+
+    let directory = match recording_settings.directory {
+        Some(directory) => PathBuf::from(directory),
+        None => app_handle.path().video_dir()?.join("rt"),
+    };
+
+    let used_bytes = apply_retention(
+        users_db,
+        &directory,
+        recording_settings.max_disk_usage,
+        recording_settings.retention_days,
+    )
+    .await?;
+
+    let max_bytes = recording_settings
+        .max_disk_usage
+        .map(|max| max.saturating_sub(used_bytes) / users.len() as u64);
+
+    if max_bytes == Some(0) {
+        error!("Recordings directory is over the disk usage limit, not auto recording");
+        return Ok(());
+    }
+
+    for user in users {
+        info!("'{}' went live, starting auto recording", user.username);
+
+        let app_handle = app_handle.clone();
+        let directory = directory.clone();
+
+        async_runtime::spawn(async move {
+            if let Err(err) = recorder::start(
+                app_handle,
+                user.username.clone(),
+                user.quality,
+                directory,
+                false,
+                max_bytes,
+            )
+            .await
+            {
+                error!("Failed to auto record '{}': {err}", user.username);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn query_auto_record_users(db: &Pool<Sqlite>) -> Result<Vec<AutoRecordUser>> {
+    let query = "SELECT username, quality FROM twitch_auto_record";
+
+    let rows = sqlx::query(query).fetch_all(db).await?;
+
+    let mut users = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        users.push(AutoRecordUser {
+            username: row.try_get("username")?,
+            quality: row.try_get("quality")?,
+        });
+    }
+
+    Ok(users)
+}
+
+async fn query_recording_settings(db: &Pool<Sqlite>) -> Result<RecordingSettings> {
+    let directory = settings::get_setting(db, DIRECTORY_SETTING).await?;
+
+    let max_disk_usage = settings::get_setting(db, MAX_DISK_USAGE_SETTING)
+        .await?
+        .and_then(|value| value.parse().ok());
+
+    let retention_days = settings::get_setting(db, RETENTION_DAYS_SETTING)
+        .await?
+        .and_then(|value| value.parse().ok());
+
+    Ok(RecordingSettings {
+        directory,
+        max_disk_usage,
+        retention_days,
+    })
+}
+
+/// Removes recordings older than the retention period, then the oldest ones until the directory is under the limit.
+///
+/// Only files the recorder wrote to the directory are touched, anything else the user keeps there is left alone,
+/// recordings still being written are never removed.
+///
+/// Returns the size in bytes of the recordings left.
+async fn apply_retention(
+    users_db: &Pool<Sqlite>,
+    directory: &Path,
+    max_disk_usage: Option<u64>,
+    retention_days: Option<u64>,
+) -> Result<u64> {
+    let mut recordings: Vec<(PathBuf, SystemTime, u64)> = Vec::new();
+
+    for path in recorder::recorded_files(users_db).await? {
+        if path.parent() != Some(directory) {
+            continue;
+        }
+
+        let metadata = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                // Removed by the user
+                recorder::forget_recording(users_db, &path).await?;
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        recordings.push((path, metadata.modified()?, metadata.len()));
+    }
+
+    // Oldest first
+    recordings.sort_by_key(|(_, modified, _)| *modified);
+
+    // Periods too long to represent can't have anything older than them
+    let cutoff = retention_days
+        .and_then(|days| days.checked_mul(24 * 60 * 60))
+        .and_then(|seconds| SystemTime::now().checked_sub(Duration::from_secs(seconds)));
+
+    if let Some(cutoff) = cutoff {
+        let mut kept = Vec::with_capacity(recordings.len());
+
+        for (path, modified, size) in recordings {
+            let removed = modified < cutoff
+                && !recorder::is_recording_path(&path).await
+                && remove_recording(users_db, &path).await;
+
+            if !removed {
+                kept.push((path, modified, size));
+            }
+        }
+
+        recordings = kept;
+    }
+
+    let mut used_bytes: u64 = recordings.iter().map(|(_, _, size)| size).sum();
+
+    if let Some(max) = max_disk_usage {
+        for (path, _, size) in &recordings {
+            if used_bytes <= max {
+                break;
+            }
+
+            if recorder::is_recording_path(path).await {
+                continue;
+            }
+
+            if remove_recording(users_db, path).await {
+                used_bytes -= size;
+            }
+        }
+    }
+
+    Ok(used_bytes)
+}
+
+/// Returns whether the file was deleted, recordings that couldn't be still count towards the disk usage.
+async fn remove_recording(users_db: &Pool<Sqlite>, path: &Path) -> bool {
+    info!("Removing old recording '{}'", path.display());
+
+    if let Err(err) = tokio::fs::remove_file(path).await {
+        error!("Failed to remove recording '{}': {err}", path.display());
+        return false;
+    }
+
+    if let Err(err) = recorder::forget_recording(users_db, path).await {
+        error!("Failed to forget recording '{}': {err}", path.display());
+    }
+
+    true
+}
@@ -0,0 +1,92 @@
+use serde::Serialize;
+
+use super::{
+    main,
+    query::{ClipData, GraphQLQuery},
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Clip {
+    pub slug: String,
+    pub title: String,
+    pub username: Option<String>,
+    /// Duration in seconds.
+    pub duration: u64,
+    /// Best quality first.
+    pub qualities: Vec<ClipQuality>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipQuality {
+    /// Height of the video, e.g. `1080`.
+    pub quality: String,
+    pub frame_rate: f64,
+    /// Signed MP4 URL.
+    pub url: String,
+}
+
+/// Resolves a clip to the MP4 URLs of its qualities.
+#[tauri::command]
+pub async fn fetch_clip(slug: &str) -> Result<Clip, String> {
+    if slug.is_empty() {
+        return Err(String::from("No clip slug provided"));
+    }
+
+    if !slug
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("Invalid clip slug '{slug}'"));
+    }
+
+    let gql = GraphQLQuery::clip_query(slug);
+
+    let response: ClipData = match main::send_query(gql).await {
+        Ok(response) => response,
+        Err(err) => {
+            return Err(format!("Failed to fetch clip: {err}"));
+        }
+    };
+
+    let Some(clip) = response.clip else {
+        return Err(format!("Clip '{slug}' not found"));
+    };
+
+    let token = clip.playback_access_token;
+
+    let mut qualities: Vec<ClipQuality> = clip
+        .video_qualities
+        .into_iter()
+        .map(|quality| ClipQuality {
+            url: format!(
+                "{}?sig={}&token={}",
+                quality.source_url,
+                token.signature,
+                urlencoding::encode(&token.value)
+            ),
+            quality: quality.quality,
+            frame_rate: quality.frame_rate,
+        })
+        .collect();
+
+    qualities.sort_by_key(|quality| {
+        std::cmp::Reverse((
+            quality.quality.parse::<u64>().unwrap_or_default(),
+            quality.frame_rate.round() as u64,
+        ))
+    });
+
+    if qualities.is_empty() {
+        return Err(format!("Clip '{slug}' has no playable qualities"));
+    }
+
+    Ok(Clip {
+        slug: slug.to_string(),
+        title: clip.title,
+        username: clip.broadcaster.map(|broadcaster| broadcaster.login),
+        duration: clip.duration_seconds,
+        qualities,
+    })
+}
@@ -0,0 +1,171 @@
+use serde::Serialize;
+
+use super::{
+    main,
+    query::{
+        CategoryStreamsData, GraphQLQuery, GraphQLResponseStreamConnection, TopCategoriesData,
+        TopStreamsData,
+    },
+    stream::LiveNow,
+};
+
+/// Items per page when no limit is provided.
+const DEFAULT_PAGE_SIZE: u32 = 30;
+
+/// Twitch doesn't return more than this per page.
+const MAX_PAGE_SIZE: u32 = 100;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Category {
+    pub id: String,
+    /// Used to fetch the streams of the category.
+    pub name: String,
+    pub display_name: String,
+    pub viewers: i64,
+    /// URL of the category cover.
+    pub box_art: String,
+}
+
+#[derive(Serialize)]
+pub struct CategoryPage {
+    pub categories: Vec<Category>,
+    /// Pass it back to fetch the next page, `None` if this is the last one.
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct StreamPage {
+    pub streams: Vec<LiveNow>,
+    /// Pass it back to fetch the next page, `None` if this is the last one.
+    pub cursor: Option<String>,
+}
+
+/// Returns the categories with the most viewers.
+#[tauri::command]
+pub async fn get_top_categories(
+    cursor: Option<String>,
+    limit: Option<u32>,
+) -> Result<CategoryPage, String> {
+    let gql = GraphQLQuery::top_categories(page_size(limit), cursor.as_deref());
+
+    let response: TopCategoriesData = match main::send_query(gql).await {
+        Ok(response) => response,
+        Err(err) => {
+            return Err(format!("Failed to fetch top categories: {err}"));
+        }
+    };
+
+    let Some(games) = response.games else {
+        return Err(String::from("No categories returned"));
+    };
+
+    let cursor = games
+        .page_info
+        .has_next_page
+        .then(|| games.edges.last().and_then(|edge| edge.cursor.clone()))
+        .flatten();
+
+    let categories = games
+        .edges
+        .into_iter()
+        .map(|edge| {
+            let game = edge.node;
+
+            Category {
+                id: game.id,
+                display_name: game.display_name,
+                name: game.name,
+                viewers: game.viewers_count.unwrap_or_default(),
+                box_art: game.box_art_url.unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    Ok(CategoryPage { categories, cursor })
+}
+
+/// Returns the live streams of a category, most viewers first.
+///
+/// `languages` are codes like `en`, only streams in them are returned if any are provided.
+#[tauri::command]
+pub async fn get_category_streams(
+    category: String,
+    cursor: Option<String>,
+    languages: Option<Vec<String>>,
+    limit: Option<u32>,
+) -> Result<StreamPage, String> {
+    if category.is_empty() {
+        return Err(String::from("No category provided"));
+    }
+
+    let gql = GraphQLQuery::category_streams(
+        &category,
+        page_size(limit),
+        cursor.as_deref(),
+        &languages.unwrap_or_default(),
+    );
+
+    let response: CategoryStreamsData = match main::send_query(gql).await {
+        Ok(response) => response,
+        Err(err) => {
+            return Err(format!("Failed to fetch streams of '{category}': {err}"));
+        }
+    };
+
+    let Some(streams) = response.game.and_then(|game| game.streams) else {
+        return Err(format!("Category '{category}' not found"));
+    };
+
+    Ok(stream_page(streams))
+}
+
+/// Returns the live streams with the most viewers across every category.
+#[tauri::command]
+pub async fn get_top_streams(
+    cursor: Option<String>,
+    languages: Option<Vec<String>>,
+    limit: Option<u32>,
+) -> Result<StreamPage, String> {
+    let gql = GraphQLQuery::top_streams(
+        page_size(limit),
+        cursor.as_deref(),
+        &languages.unwrap_or_default(),
+    );
+
+    let response: TopStreamsData = match main::send_query(gql).await {
+        Ok(response) => response,
+        Err(err) => {
+            return Err(format!("Failed to fetch top streams: {err}"));
+        }
+    };
+
+    let Some(streams) = response.streams else {
+        return Err(String::from("No streams returned"));
+    };
+
+    Ok(stream_page(streams))
+}
+
+fn page_size(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+fn stream_page(connection: GraphQLResponseStreamConnection) -> StreamPage {
+    let cursor = connection
+        .page_info
+        .has_next_page
+        .then(|| connection.edges.last().and_then(|edge| edge.cursor.clone()))
+        .flatten();
+
+    let streams = connection
+        .edges
+        .into_iter()
+        .filter_map(|edge| {
+            let username = edge.node.broadcaster?.login;
+            Some(LiveNow::from_stream(username, edge.node.stream))
+        })
+        .collect();
+
+    StreamPage { streams, cursor }
+}
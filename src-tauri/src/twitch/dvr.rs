@@ -0,0 +1,392 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::{anyhow, Result};
+use hyper::body::Bytes;
+use lazy_static::lazy_static;
+use log::error;
+use tauri::async_runtime::{self, Mutex};
+
+use super::{
+    m3u8::{MediaPlaylist, Segment},
+    proxy,
+};
+
+/// How far back, in seconds, a live stream can be rewound.
+const DVR_WINDOW: f64 = 30.0 * 60.0;
+
+/// Size of the segments cached across every buffer, the oldest segments are dropped past it.
+///
+/// The cache is in the temp directory, which may be in memory, 30 minutes of every buffer would not fit.
+const MAX_CACHE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+lazy_static! {
+    static ref DVR_BUFFERS: Mutex<HashMap<u64, DvrBuffer>> = Mutex::new(HashMap::new());
+    static ref NEXT_BUFFER_ID: AtomicU64 = AtomicU64::new(0);
+}
+
+/// Segments of a media playlist kept after Twitch drops them from the live playlist.
+///
+/// There is one buffer per media playlist the player requests, every buffer numbers its segments on its own,
+/// starting from the media sequence of the first playlist seen, so playlists of different qualities stay aligned.
+struct DvrBuffer {
+    window_label: String,
+    username: String,
+    media_url: String,
+    media_sequence: u64,
+    discontinuity_sequence: u64,
+    segments: VecDeque<DvrSegment>,
+    duration: f64,
+    /// Size of the segments of the buffer cached to disk.
+    cached_bytes: u64,
+    /// If the last segments came from the backup stream, switching streams needs a discontinuity.
+    from_backup: Option<bool>,
+}
+
+struct DvrSegment {
+    sequence: u64,
+    /// Segment as received, with the upstream URI.
+    segment: Segment,
+    /// Size of the cached file, `0` until it's cached.
+    cached_bytes: u64,
+}
+
+impl DvrBuffer {
+    /// Segments are matched by their program date time, the main and backup streams share the same clock.
+    fn contains(&self, segment: &Segment) -> bool {
+        // Prefetched segments don't have one until they are complete
+        let last_date = self
+            .segments
+            .iter()
+            .rev()
+            .find_map(|s| s.segment.program_date_time());
+
+        match (segment.program_date_time(), last_date) {
+            // Twitch always uses the same format, so comparing them as strings is enough
+            (Some(date), Some(last_date)) => date <= last_date,
+            _ => self.segments.iter().any(|s| s.segment.uri == segment.uri),
+        }
+    }
+
+    /// Adds the segments that are not in the buffer yet.
+    ///
+    /// Returns the sequence and upstream URI of the added segments, and the sequence of the ones that rolled out of the window.
+    fn append(
+        &mut self,
+        playlist: &MediaPlaylist,
+        from_backup: bool,
+    ) -> (Vec<(u64, String)>, Vec<u64>) {
+        let mut switched_stream = self
+            .from_backup
+            .is_some_and(|previous| previous != from_backup);
+
+        self.from_backup = Some(from_backup);
+
+        let mut added = Vec::new();
+
+        for (i, segment) in playlist.segments.iter().enumerate() {
+            // Prefetched segments have no program date time, they get it with their actual duration once complete
+            if let Some(prefetched) = self.segments.iter_mut().find(|s| {
+                s.segment.uri == segment.uri
+                    && s.segment.program_date_time().is_none()
+                    && segment.program_date_time().is_some()
+            }) {
+                let discontinuity = prefetched.segment.is_discontinuity();
+
+                self.duration += segment.duration() - prefetched.segment.duration();
+                prefetched.segment = segment.clone();
+
+                if discontinuity {
+                    prefetched.segment.set_discontinuity();
+                }
+
+                continue;
+            }
+
+            if self.contains(segment) {
+                continue;
+            }
+
+            let sequence = match self.segments.back() {
+                Some(last) => last.sequence + 1,
+                None => {
+                    self.media_sequence = playlist.media_sequence() + i as u64;
+                    self.media_sequence
+                }
+            };
+
+            let mut segment = segment.clone();
+
+            if switched_stream {
+                segment.set_discontinuity();
+                switched_stream = false;
+            }
+
+            self.duration += segment.duration();
+            added.push((sequence, segment.uri.clone()));
+            self.segments.push_back(DvrSegment {
+                sequence,
+                segment,
+                cached_bytes: 0,
+            });
+        }
+
+        let mut removed = Vec::new();
+
+        while self.duration > DVR_WINDOW && self.segments.len() > 1 {
+            let Some(first) = self.remove_first() else {
+                break;
+            };
+
+            removed.push(first.sequence);
+        }
+
+        (added, removed)
+    }
+
+    /// Removes the oldest segment, the playlist starts at the next one.
+    fn remove_first(&mut self) -> Option<DvrSegment> {
+        let first = self.segments.pop_front()?;
+
+        self.duration -= first.segment.duration();
+        self.cached_bytes -= first.cached_bytes;
+        self.media_sequence = first.sequence + 1;
+
+        if first.segment.is_discontinuity() {
+            self.discontinuity_sequence += 1;
+        }
+
+        Some(first)
+    }
+
+    fn to_playlist(&self, id: u64, playlist: &MediaPlaylist) -> MediaPlaylist {
+        let segments = self
+            .segments
+            .iter()
+            .map(|dvr_segment| {
+                let mut segment = dvr_segment.segment.clone();
+                segment.uri = format!("/twitch/dvr/{id}/{}.ts", dvr_segment.sequence);
+                segment
+            })
+            .collect();
+
+        let mut dvr_playlist = MediaPlaylist {
+            tags: playlist.tags.clone(),
+            segments,
+            trailing_tags: playlist.trailing_tags.clone(),
+            prefetch: playlist.prefetch.clone(),
+            end_list: playlist.end_list,
+        };
+
+        dvr_playlist.set_media_sequence(self.media_sequence);
+        dvr_playlist.set_discontinuity_sequence(self.discontinuity_sequence);
+
+        dvr_playlist
+    }
+}
+
+/// Adds the segments of a playlist to its DVR buffer and returns the whole buffer as a playlist.
+///
+/// Segment URIs point to `/twitch/dvr/<buffer>/<sequence>.ts`, segments are downloaded and cached to disk in the
+/// background as soon as they are added, so they can still be played after Twitch stops serving them,
+/// even if the player never requested them, e.g. while playing another quality.
+pub async fn buffer_playlist(
+    window_label: &str,
+    username: &str,
+    media_url: &str,
+    playlist: &MediaPlaylist,
+    from_backup: bool,
+) -> MediaPlaylist {
+    let mut buffers = DVR_BUFFERS.lock().await;
+
+    let id = buffers
+        .iter()
+        .find(|(_, buffer)| {
+            buffer.window_label == window_label
+                && buffer.username == username
+                && buffer.media_url == media_url
+        })
+        .map(|(id, _)| *id);
+
+    let id = id.unwrap_or_else(|| {
+        let id = NEXT_BUFFER_ID.fetch_add(1, Ordering::Relaxed);
+
+        buffers.insert(
+            id,
+            DvrBuffer {
+                window_label: window_label.to_string(),
+                username: username.to_string(),
+                media_url: media_url.to_string(),
+                media_sequence: 0,
+                discontinuity_sequence: playlist.discontinuity_sequence(),
+                segments: VecDeque::new(),
+                duration: 0.0,
+                cached_bytes: 0,
+                from_backup: None,
+            },
+        );
+
+        id
+    });
+
+    let buffer = buffers.get_mut(&id).unwrap();
+    let (added, removed) = buffer.append(playlist, from_backup);
+
+    for sequence in removed {
+        remove_segment_file(id, sequence).await;
+    }
+
+    for (sequence, uri) in added {
+        async_runtime::spawn(async move {
+            if let Err(err) = download_segment(id, sequence, &uri).await {
+                error!("Failed to cache DVR segment {sequence} of buffer {id}: {err}");
+            }
+        });
+    }
+
+    buffer.to_playlist(id, playlist)
+}
+
+/// Returns a segment from the disk cache, or fetches and caches it if it's not there yet.
+pub async fn fetch_segment(id: u64, sequence: u64) -> Result<Bytes> {
+    let path = segment_path(id, sequence);
+
+    if let Ok(bytes) = tokio::fs::read(&path).await {
+        return Ok(Bytes::from(bytes));
+    }
+
+    let url = {
+        let buffers = DVR_BUFFERS.lock().await;
+
+        buffers
+            .get(&id)
+            .and_then(|buffer| buffer.segments.iter().find(|s| s.sequence == sequence))
+            .map(|s| s.segment.uri.clone())
+            .ok_or_else(|| anyhow!("Segment {sequence} is not in DVR buffer {id}"))?
+    };
+
+    // Still being downloaded in the background, or it couldn't be cached,
+    // segments dropped to stay under the cache size limit are removed from the buffer too
+    download_segment(id, sequence, &url).await
+}
+
+/// Downloads a segment of a buffer and caches it, unless it was removed from the buffer in the meantime.
+async fn download_segment(id: u64, sequence: u64, url: &str) -> Result<Bytes> {
+    let bytes = proxy::fetch_segment(url).await?;
+
+    let mut buffers = DVR_BUFFERS.lock().await;
+
+    // Locked while writing, so segments that rolled out or buffers that were removed are not cached again
+    let Some(buffer) = buffers.get_mut(&id) else {
+        return Ok(bytes);
+    };
+
+    let Some(segment) = buffer
+        .segments
+        .iter_mut()
+        .find(|segment| segment.sequence == sequence)
+    else {
+        return Ok(bytes);
+    };
+
+    if segment.cached_bytes > 0 {
+        return Ok(bytes);
+    }
+
+    let path = segment_path(id, sequence);
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    if let Err(err) = tokio::fs::write(&path, &bytes).await {
+        error!("Failed to cache DVR segment '{}': {err}", path.display());
+        return Ok(bytes);
+    }
+
+    segment.cached_bytes = bytes.len() as u64;
+    buffer.cached_bytes += bytes.len() as u64;
+
+    limit_cache_size(&mut buffers).await;
+
+    Ok(bytes)
+}
+
+/// Drops the oldest segments of the buffers with the most cached segments until the cache fits in `MAX_CACHE_BYTES`.
+async fn limit_cache_size(buffers: &mut HashMap<u64, DvrBuffer>) {
+    let mut total: u64 = buffers.values().map(|buffer| buffer.cached_bytes).sum();
+
+    while total > MAX_CACHE_BYTES {
+        let Some((id, buffer)) = buffers
+            .iter_mut()
+            .filter(|(_, buffer)| buffer.segments.len() > 1)
+            .max_by_key(|(_, buffer)| buffer.cached_bytes)
+        else {
+            break;
+        };
+
+        let Some(first) = buffer.remove_first() else {
+            break;
+        };
+
+        total -= first.cached_bytes;
+
+        if first.cached_bytes > 0 {
+            remove_segment_file(*id, first.sequence).await;
+        }
+    }
+}
+
+async fn remove_segment_file(id: u64, sequence: u64) {
+    let path = segment_path(id, sequence);
+
+    if path.exists() {
+        if let Err(err) = tokio::fs::remove_file(&path).await {
+            error!("Failed to remove DVR segment '{}': {err}", path.display());
+        }
+    }
+}
+
+/// Removes the buffers of every stream played in a window, along with their cached segments.
+pub async fn remove_window_buffers(window_label: &str) {
+    let mut buffers = DVR_BUFFERS.lock().await;
+
+    let ids: Vec<u64> = buffers
+        .iter()
+        .filter(|(_, buffer)| buffer.window_label == window_label)
+        .map(|(id, _)| *id)
+        .collect();
+
+    for id in ids {
+        buffers.remove(&id);
+        remove_cache_dir(cache_dir().join(id.to_string())).await;
+    }
+}
+
+/// Removes segments cached by a previous run of the app.
+pub async fn clear_cache() {
+    remove_cache_dir(cache_dir()).await;
+}
+
+async fn remove_cache_dir(dir: PathBuf) {
+    if !dir.exists() {
+        return;
+    }
+
+    if let Err(err) = tokio::fs::remove_dir_all(&dir).await {
+        error!("Failed to remove DVR cache '{}': {err}", dir.display());
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    std::env::temp_dir().join("rt-dvr")
+}
+
+fn segment_path(id: u64, sequence: u64) -> PathBuf {
+    cache_dir()
+        .join(id.to_string())
+        .join(format!("{sequence}.ts"))
+}
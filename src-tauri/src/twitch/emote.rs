@@ -1,9 +1,10 @@
-use std::collections::HashMap;
-
-use anyhow::{anyhow, Context, Result};
+use std::{collections::HashMap, fmt, time::Duration};
 
+use anyhow::Result;
+use log::warn;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sqlx::{Pool, Row, Sqlite};
+use tauri_plugin_http::reqwest::{header::RETRY_AFTER, StatusCode};
 
 use super::main::HTTP_CLIENT;
 
@@ -11,6 +12,62 @@ pub const TWITCH_EMOTES_CDN: &str = "https://static-cdn.jtvnw.net/emoticons/v2";
 const SEVENTV_API: &str = "https://7tv.io/v3";
 const BETTERTV_API: &str = "https://api.betterttv.net/3";
 
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum EmoteProviderError {
+    /// The provider has no data for this user, e.g. the user never created a 7TV account.
+    NotFound,
+    RateLimited {
+        retry_after: Option<Duration>,
+    },
+    /// Either the request never reached the provider (no status) or it answered with an error status.
+    Upstream {
+        status: Option<StatusCode>,
+        message: String,
+    },
+    Decode(String),
+}
+
+impl EmoteProviderError {
+    fn is_transient(&self) -> bool {
+        match self {
+            Self::RateLimited { .. } => true,
+            Self::Upstream { status, .. } => status.is_none_or(|status| status.is_server_error()),
+            Self::NotFound | Self::Decode(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for EmoteProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "Emote provider has no data for this user"),
+            Self::RateLimited {
+                retry_after: Some(retry_after),
+            } => write!(
+                f,
+                "Rate limited by emote provider, retry after {}s",
+                retry_after.as_secs()
+            ),
+            Self::RateLimited { retry_after: None } => write!(f, "Rate limited by emote provider"),
+            Self::Upstream {
+                status: Some(status),
+                message,
+            } => write!(f, "Emote provider returned {status}: {message}"),
+            Self::Upstream {
+                status: None,
+                message,
+            } => write!(f, "Failed to reach emote provider: {message}"),
+            Self::Decode(message) => write!(f, "Failed to deserialize emotes response: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for EmoteProviderError {}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Emote {
     #[serde(rename = "n")]
@@ -117,11 +174,17 @@ struct BetterTTVEmote {
     height: Option<i64>,
 }
 
-pub async fn fetch_bettertv_emotes(id: &str) -> Result<HashMap<String, Emote>> {
-    let response = fetch_and_deserialize::<BetterTTVResponse>(&format!(
+pub async fn fetch_bettertv_emotes(id: &str) -> Result<HashMap<String, Emote>, EmoteProviderError> {
+    let response = match fetch_and_deserialize::<BetterTTVResponse>(&format!(
         "{BETTERTV_API}/cached/users/twitch/{id}"
     ))
-    .await?;
+    .await
+    {
+        Ok(response) => response,
+        // User never connected their account to BetterTTV
+        Err(EmoteProviderError::NotFound) => return Ok(HashMap::new()),
+        Err(err) => return Err(err),
+    };
 
     let raw_emotes = [&response.channel_emotes[..], &response.shared_emotes[..]].concat();
 
@@ -146,7 +209,8 @@ pub async fn fetch_bettertv_emotes(id: &str) -> Result<HashMap<String, Emote>> {
 
 #[derive(Deserialize)]
 struct SevenTVResponse {
-    emote_set: SevenTVEmoteSet,
+    // Accounts without an active emote set have this as null
+    emote_set: Option<SevenTVEmoteSet>,
 }
 
 #[derive(Deserialize)]
@@ -179,14 +243,24 @@ struct SevenTVEmoteDataHostFile {
     format: String,
 }
 
-pub async fn fetch_7tv_emotes(id: &str) -> Result<HashMap<String, Emote>> {
+pub async fn fetch_7tv_emotes(id: &str) -> Result<HashMap<String, Emote>, EmoteProviderError> {
     let response =
-        fetch_and_deserialize::<SevenTVResponse>(&format!("{SEVENTV_API}/users/twitch/{id}"))
-            .await?;
+        match fetch_and_deserialize::<SevenTVResponse>(&format!("{SEVENTV_API}/users/twitch/{id}"))
+            .await
+        {
+            Ok(response) => response,
+            // User never created a 7TV account
+            Err(EmoteProviderError::NotFound) => return Ok(HashMap::new()),
+            Err(err) => return Err(err),
+        };
 
     let mut emotes: HashMap<String, Emote> = HashMap::new();
 
-    for mut emote in response.emote_set.emotes {
+    let Some(emote_set) = response.emote_set else {
+        return Ok(emotes);
+    };
+
+    for mut emote in emote_set.emotes {
         emote
             .data
             .host
@@ -235,26 +309,78 @@ pub async fn fetch_7tv_emotes(id: &str) -> Result<HashMap<String, Emote>> {
     Ok(emotes)
 }
 
-async fn fetch_and_deserialize<T: DeserializeOwned>(url: &str) -> Result<T> {
-    let response = HTTP_CLIENT
-        .get(url)
-        .send()
-        .await
-        .context("Failed to send emotes request")?;
+/// Fetches and deserializes a provider response, retrying rate limits and transient upstream failures.
+async fn fetch_and_deserialize<T: DeserializeOwned>(url: &str) -> Result<T, EmoteProviderError> {
+    let mut attempt = 1;
 
-    let status = response.status();
+    loop {
+        let err = match try_fetch_and_deserialize(url).await {
+            Ok(response) => return Ok(response),
+            Err(err) => err,
+        };
+
+        if attempt >= MAX_ATTEMPTS || !err.is_transient() {
+            return Err(err);
+        }
+
+        let delay = match err {
+            EmoteProviderError::RateLimited {
+                retry_after: Some(retry_after),
+            } => retry_after.min(MAX_RETRY_AFTER),
+            _ => RETRY_DELAY * attempt,
+        };
+
+        warn!("Emotes request to '{url}' failed (attempt {attempt}/{MAX_ATTEMPTS}): {err}, retrying in {delay:?}");
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
 
-    if !status.is_success() && status == 404 {
-        let error_body = response
-            .text()
+async fn try_fetch_and_deserialize<T: DeserializeOwned>(
+    url: &str,
+) -> Result<T, EmoteProviderError> {
+    let response =
+        HTTP_CLIENT
+            .get(url)
+            .send()
             .await
-            .context("Failed to read response body")?;
+            .map_err(|err| EmoteProviderError::Upstream {
+                status: None,
+                message: err.to_string(),
+            })?;
+
+    let status = response.status();
+
+    if status == StatusCode::NOT_FOUND {
+        return Err(EmoteProviderError::NotFound);
+    }
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
 
-        return Err(anyhow!("Request failed with status {status}: {error_body}"));
+        return Err(EmoteProviderError::RateLimited { retry_after });
     }
 
-    response
-        .json()
+    let body = response
+        .text()
         .await
-        .context("Failed to deserialize emotes response")
+        .map_err(|err| EmoteProviderError::Upstream {
+            status: Some(status),
+            message: format!("Failed to read response body: {err}"),
+        })?;
+
+    if !status.is_success() {
+        return Err(EmoteProviderError::Upstream {
+            status: Some(status),
+            message: body,
+        });
+    }
+
+    serde_json::from_str(&body).map_err(|err| EmoteProviderError::Decode(err.to_string()))
 }
@@ -0,0 +1,781 @@
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+
+/// Typed representation of the HLS playlists served by Twitch.
+///
+/// Only the tags we care about are parsed into fields, everything else is kept as raw lines
+/// so a parsed playlist can be written back without losing information.
+pub enum Playlist {
+    Master(MasterPlaylist),
+    Media(MediaPlaylist),
+}
+
+impl Playlist {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+
+        if lines.next() != Some("#EXTM3U") {
+            return Err(anyhow!("Playlist is missing the #EXTM3U header"));
+        }
+
+        if text
+            .lines()
+            .any(|line| line.trim_start().starts_with("#EXT-X-STREAM-INF"))
+        {
+            MasterPlaylist::parse(lines).map(Self::Master)
+        } else {
+            MediaPlaylist::parse(lines).map(Self::Media)
+        }
+    }
+}
+
+impl fmt::Display for Playlist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Master(playlist) => playlist.fmt(f),
+            Self::Media(playlist) => playlist.fmt(f),
+        }
+    }
+}
+
+/// Ordered list of `KEY=VALUE` pairs, values are kept as written, including quotes.
+#[derive(Clone, Default)]
+pub struct AttributeList(Vec<(String, String)>);
+
+impl AttributeList {
+    pub fn parse(input: &str) -> Self {
+        let mut attributes = Vec::new();
+        let mut rest = input.trim();
+
+        while !rest.is_empty() {
+            let Some((key, value)) = rest.split_once('=') else {
+                break;
+            };
+
+            // Quoted values may contain commas
+            let end = if let Some(quoted) = value.strip_prefix('"') {
+                quoted.find('"').map_or(value.len(), |i| i + 2)
+            } else {
+                value.find(',').unwrap_or(value.len())
+            };
+
+            attributes.push((key.trim().to_string(), value[..end].to_string()));
+
+            rest = value[end..].trim_start_matches(',').trim_start();
+        }
+
+        Self(attributes)
+    }
+
+    /// Returns the value of an attribute without quotes.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.trim_matches('"'))
+    }
+}
+
+impl fmt::Display for AttributeList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+
+            write!(f, "{key}={value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct MasterPlaylist {
+    pub entries: Vec<MasterEntry>,
+}
+
+/// Twitch interleaves media groups and variants, so they are kept in a single list to preserve the order.
+pub enum MasterEntry {
+    Media(Media),
+    Variant(Variant),
+    Tag(String),
+}
+
+/// `#EXT-X-MEDIA`, a rendition in a media group.
+#[derive(Clone)]
+pub struct Media {
+    pub attributes: AttributeList,
+}
+
+impl Media {
+    pub fn group_id(&self) -> Option<&str> {
+        self.attributes.get("GROUP-ID")
+    }
+
+    /// Twitch uses it as the display name of the quality, e.g. `1080p60 (source)`.
+    pub fn name(&self) -> Option<&str> {
+        self.attributes.get("NAME")
+    }
+}
+
+/// `#EXT-X-STREAM-INF` and the URI of its media playlist.
+#[derive(Clone)]
+pub struct Variant {
+    pub attributes: AttributeList,
+    /// Tags between `#EXT-X-STREAM-INF` and the URI.
+    pub tags: Vec<String>,
+    pub uri: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Resolution {
+    pub width: u64,
+    pub height: u64,
+}
+
+impl Variant {
+    pub fn bandwidth(&self) -> u64 {
+        self.attributes
+            .get("BANDWIDTH")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn resolution(&self) -> Option<Resolution> {
+        let (width, height) = self.attributes.get("RESOLUTION")?.split_once('x')?;
+
+        Some(Resolution {
+            width: width.parse().ok()?,
+            height: height.parse().ok()?,
+        })
+    }
+
+    pub fn frame_rate(&self) -> Option<f64> {
+        self.attributes.get("FRAME-RATE")?.parse().ok()
+    }
+
+    pub fn codecs(&self) -> Option<&str> {
+        self.attributes.get("CODECS")
+    }
+
+    /// Group ID of the video rendition, Twitch uses it as the quality identifier, e.g. `chunked` or `720p60`.
+    pub fn video(&self) -> Option<&str> {
+        self.attributes.get("VIDEO")
+    }
+
+    /// Twitch's `audio_only` variant, it is in the video group of the same name but has no video.
+    pub fn is_audio_only(&self) -> bool {
+        self.video() == Some("audio_only")
+    }
+}
+
+impl MasterPlaylist {
+    fn parse<'a>(mut lines: impl Iterator<Item = &'a str>) -> Result<Self> {
+        let mut entries = Vec::new();
+
+        while let Some(line) = lines.next() {
+            if let Some(attributes) = line.strip_prefix("#EXT-X-MEDIA:") {
+                entries.push(MasterEntry::Media(Media {
+                    attributes: AttributeList::parse(attributes),
+                }));
+            } else if let Some(attributes) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+                let mut tags = Vec::new();
+
+                let uri = loop {
+                    match lines.next() {
+                        Some(line) if line.starts_with('#') => tags.push(line.to_string()),
+                        Some(uri) => break uri,
+                        None => return Err(anyhow!("Variant is missing its URI")),
+                    }
+                };
+
+                entries.push(MasterEntry::Variant(Variant {
+                    attributes: AttributeList::parse(attributes),
+                    tags,
+                    uri: uri.to_string(),
+                }));
+            } else {
+                entries.push(MasterEntry::Tag(line.to_string()));
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn variants(&self) -> impl Iterator<Item = &Variant> {
+        self.entries.iter().filter_map(|entry| match entry {
+            MasterEntry::Variant(variant) => Some(variant),
+            _ => None,
+        })
+    }
+
+    /// Returns the media rendition of a group.
+    pub fn media(&self, group_id: &str) -> Option<&Media> {
+        self.entries.iter().find_map(|entry| match entry {
+            MasterEntry::Media(media) if media.group_id() == Some(group_id) => Some(media),
+            _ => None,
+        })
+    }
+
+    /// Keeps the variants matching `keep`, along with the media renditions they use.
+    pub fn retain_variants(&mut self, keep: impl Fn(&Variant) -> bool) {
+        let kept_groups: Vec<String> = self
+            .variants()
+            .filter(|variant| keep(variant))
+            .filter_map(|variant| variant.video().map(str::to_string))
+            .collect();
+
+        self.entries.retain(|entry| match entry {
+            MasterEntry::Variant(variant) => keep(variant),
+            MasterEntry::Media(media) => media
+                .group_id()
+                .is_none_or(|group_id| kept_groups.iter().any(|kept| kept == group_id)),
+            MasterEntry::Tag(_) => true,
+        });
+    }
+}
+
+impl fmt::Display for MasterPlaylist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#EXTM3U")?;
+
+        for entry in &self.entries {
+            match entry {
+                MasterEntry::Media(media) => writeln!(f, "#EXT-X-MEDIA:{}", media.attributes)?,
+                MasterEntry::Variant(variant) => {
+                    writeln!(f, "#EXT-X-STREAM-INF:{}", variant.attributes)?;
+
+                    for tag in &variant.tags {
+                        writeln!(f, "{tag}")?;
+                    }
+
+                    writeln!(f, "{}", variant.uri)?;
+                }
+                MasterEntry::Tag(tag) => writeln!(f, "{tag}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct MediaPlaylist {
+    /// Playlist level tags, e.g. `#EXT-X-MEDIA-SEQUENCE` and `#EXT-X-TWITCH-ELAPSED-SECS`.
+    pub tags: Vec<String>,
+    pub segments: Vec<Segment>,
+    /// Tags after the last segment, they apply to the next one.
+    pub trailing_tags: Vec<SegmentTag>,
+    /// URIs from `#EXT-X-TWITCH-PREFETCH`, segments that are not yet complete.
+    pub prefetch: Vec<String>,
+    pub end_list: bool,
+}
+
+#[derive(Clone)]
+pub struct Segment {
+    /// Tags of the segment, in the order they were written.
+    pub tags: Vec<SegmentTag>,
+    pub uri: String,
+}
+
+/// A tag that applies to the segment after it.
+#[derive(Clone)]
+pub enum SegmentTag {
+    Discontinuity,
+    ProgramDateTime(String),
+    DateRange(DateRange),
+    /// `#EXTINF`, the duration is kept as written so the segment is served unchanged.
+    Info {
+        duration: String,
+        title: String,
+    },
+    Other(String),
+}
+
+impl SegmentTag {
+    fn parse(line: &str) -> Result<Self> {
+        if line == "#EXT-X-DISCONTINUITY" {
+            Ok(Self::Discontinuity)
+        } else if let Some(date) = line.strip_prefix("#EXT-X-PROGRAM-DATE-TIME:") {
+            Ok(Self::ProgramDateTime(date.to_string()))
+        } else if let Some(attributes) = line.strip_prefix("#EXT-X-DATERANGE:") {
+            Ok(Self::DateRange(DateRange {
+                attributes: AttributeList::parse(attributes),
+            }))
+        } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+            let (duration, title) = value.split_once(',').unwrap_or((value, ""));
+
+            if duration.trim().parse::<f64>().is_err() {
+                return Err(anyhow!("Invalid segment duration '{duration}'"));
+            }
+
+            Ok(Self::Info {
+                duration: duration.to_string(),
+                title: title.to_string(),
+            })
+        } else {
+            Ok(Self::Other(line.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for SegmentTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Discontinuity => f.write_str("#EXT-X-DISCONTINUITY"),
+            Self::ProgramDateTime(date) => write!(f, "#EXT-X-PROGRAM-DATE-TIME:{date}"),
+            Self::DateRange(date_range) => write!(f, "#EXT-X-DATERANGE:{}", date_range.attributes),
+            Self::Info { duration, title } => write!(f, "#EXTINF:{duration},{title}"),
+            Self::Other(line) => f.write_str(line),
+        }
+    }
+}
+
+impl Segment {
+    pub fn new(duration: &str, title: &str, uri: String) -> Self {
+        Self {
+            tags: vec![SegmentTag::Info {
+                duration: duration.to_string(),
+                title: title.to_string(),
+            }],
+            uri,
+        }
+    }
+
+    fn info(&self) -> Option<(&str, &str)> {
+        self.tags.iter().find_map(|tag| match tag {
+            SegmentTag::Info { duration, title } => Some((duration.as_str(), title.as_str())),
+            _ => None,
+        })
+    }
+
+    /// Duration as written in `#EXTINF`, used to copy it to other segments without rounding it.
+    pub fn duration_text(&self) -> &str {
+        self.info().map_or("0", |(duration, _)| duration)
+    }
+
+    /// Duration in seconds.
+    pub fn duration(&self) -> f64 {
+        self.duration_text().trim().parse().unwrap_or_default()
+    }
+
+    /// Twitch uses `live` for stream segments and the ad name for ads.
+    pub fn title(&self) -> &str {
+        self.info().map_or("", |(_, title)| title)
+    }
+
+    pub fn set_title(&mut self, new_title: &str) {
+        for tag in &mut self.tags {
+            if let SegmentTag::Info { title, .. } = tag {
+                *title = new_title.to_string();
+            }
+        }
+    }
+
+    pub fn is_discontinuity(&self) -> bool {
+        self.tags
+            .iter()
+            .any(|tag| matches!(tag, SegmentTag::Discontinuity))
+    }
+
+    /// Marks a discontinuity before the segment, if there isn't one already.
+    pub fn set_discontinuity(&mut self) {
+        if !self.is_discontinuity() {
+            self.tags.insert(0, SegmentTag::Discontinuity);
+        }
+    }
+
+    pub fn program_date_time(&self) -> Option<&str> {
+        self.tags.iter().find_map(|tag| match tag {
+            SegmentTag::ProgramDateTime(date) => Some(date.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn date_ranges(&self) -> impl Iterator<Item = &DateRange> {
+        self.tags.iter().filter_map(|tag| match tag {
+            SegmentTag::DateRange(date_range) => Some(date_range),
+            _ => None,
+        })
+    }
+
+    pub fn retain_date_ranges(&mut self, keep: impl Fn(&DateRange) -> bool) {
+        self.tags.retain(|tag| match tag {
+            SegmentTag::DateRange(date_range) => keep(date_range),
+            _ => true,
+        });
+    }
+}
+
+/// `#EXT-X-DATERANGE`, Twitch uses them to mark ads and the stream session.
+#[derive(Clone)]
+pub struct DateRange {
+    pub attributes: AttributeList,
+}
+
+impl DateRange {
+    pub fn id(&self) -> Option<&str> {
+        self.attributes.get("ID")
+    }
+
+    pub fn class(&self) -> Option<&str> {
+        self.attributes.get("CLASS")
+    }
+}
+
+const MEDIA_PLAYLIST_TAGS: [&str; 7] = [
+    "#EXT-X-VERSION",
+    "#EXT-X-TARGETDURATION",
+    "#EXT-X-MEDIA-SEQUENCE",
+    "#EXT-X-DISCONTINUITY-SEQUENCE",
+    "#EXT-X-PLAYLIST-TYPE",
+    "#EXT-X-INDEPENDENT-SEGMENTS",
+    "#EXT-X-START",
+];
+
+impl MediaPlaylist {
+    fn parse<'a>(lines: impl Iterator<Item = &'a str>) -> Result<Self> {
+        let mut playlist = Self {
+            tags: Vec::new(),
+            segments: Vec::new(),
+            trailing_tags: Vec::new(),
+            prefetch: Vec::new(),
+            end_list: false,
+        };
+
+        let mut tags = Vec::new();
+
+        for line in lines {
+            if let Some(uri) = line.strip_prefix("#EXT-X-TWITCH-PREFETCH:") {
+                playlist.prefetch.push(uri.to_string());
+            } else if line == "#EXT-X-ENDLIST" {
+                playlist.end_list = true;
+            } else if MEDIA_PLAYLIST_TAGS.iter().any(|tag| line.starts_with(tag))
+                || line.starts_with("#EXT-X-TWITCH-")
+            {
+                playlist.tags.push(line.to_string());
+            } else if line.starts_with('#') {
+                let tag = SegmentTag::parse(line)?;
+
+                // Unknown tags before the first segment, e.g. `#ID3-EQUIV-TDTG` in VODs, are about the playlist
+                if matches!(tag, SegmentTag::Other(_))
+                    && playlist.segments.is_empty()
+                    && tags.is_empty()
+                {
+                    playlist.tags.push(line.to_string());
+                } else {
+                    tags.push(tag);
+                }
+            } else {
+                let segment = Segment {
+                    tags: std::mem::take(&mut tags),
+                    uri: line.to_string(),
+                };
+
+                if segment.info().is_none() {
+                    return Err(anyhow!("Segment '{line}' is missing #EXTINF"));
+                }
+
+                playlist.segments.push(segment);
+            }
+        }
+
+        playlist.trailing_tags = tags;
+
+        Ok(playlist)
+    }
+    fn tag_value(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find_map(|tag| tag.strip_prefix(name)?.strip_prefix(':'))
+    }
+
+    fn set_tag_value(&mut self, name: &str, value: &str, after: &str) {
+        let line = format!("{name}:{value}");
+
+        if let Some(tag) = self
+            .tags
+            .iter_mut()
+            .find(|tag| tag.strip_prefix(name).is_some_and(|v| v.starts_with(':')))
+        {
+            *tag = line;
+            return;
+        }
+
+        let position = self
+            .tags
+            .iter()
+            .position(|tag| tag.starts_with(after))
+            .map_or(self.tags.len(), |i| i + 1);
+
+        self.tags.insert(position, line);
+    }
+
+    pub fn media_sequence(&self) -> u64 {
+        self.tag_value("#EXT-X-MEDIA-SEQUENCE")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn set_media_sequence(&mut self, sequence: u64) {
+        self.set_tag_value(
+            "#EXT-X-MEDIA-SEQUENCE",
+            &sequence.to_string(),
+            "#EXT-X-TARGETDURATION",
+        );
+    }
+
+    pub fn discontinuity_sequence(&self) -> u64 {
+        self.tag_value("#EXT-X-DISCONTINUITY-SEQUENCE")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn set_discontinuity_sequence(&mut self, sequence: u64) {
+        self.set_tag_value(
+            "#EXT-X-DISCONTINUITY-SEQUENCE",
+            &sequence.to_string(),
+            "#EXT-X-MEDIA-SEQUENCE",
+        );
+    }
+}
+
+impl fmt::Display for MediaPlaylist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#EXTM3U")?;
+
+        for tag in &self.tags {
+            writeln!(f, "{tag}")?;
+        }
+
+        for segment in &self.segments {
+            for tag in &segment.tags {
+                writeln!(f, "{tag}")?;
+            }
+
+            writeln!(f, "{}", segment.uri)?;
+        }
+
+        for tag in &self.trailing_tags {
+            writeln!(f, "{tag}")?;
+        }
+
+        for uri in &self.prefetch {
+            writeln!(f, "#EXT-X-TWITCH-PREFETCH:{uri}")?;
+        }
+
+        if self.end_list {
+            writeln!(f, "#EXT-X-ENDLIST")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = include_str!("../../tests/fixtures/twitch/master.m3u8");
+    const LIVE: &str = include_str!("../../tests/fixtures/twitch/live.m3u8");
+    const VOD: &str = include_str!("../../tests/fixtures/twitch/vod.m3u8");
+
+    fn parse_master(text: &str) -> MasterPlaylist {
+        match Playlist::parse(text).unwrap() {
+            Playlist::Master(playlist) => playlist,
+            Playlist::Media(_) => panic!("Expected a master playlist"),
+        }
+    }
+
+    fn parse_media(text: &str) -> MediaPlaylist {
+        match Playlist::parse(text).unwrap() {
+            Playlist::Media(playlist) => playlist,
+            Playlist::Master(_) => panic!("Expected a media playlist"),
+        }
+    }
+
+    #[test]
+    fn master_round_trip() {
+        assert_eq!(parse_master(MASTER).to_string(), MASTER);
+    }
+
+    #[test]
+    fn master_variants() {
+        let playlist = parse_master(MASTER);
+        let variants: Vec<&Variant> = playlist.variants().collect();
+
+        assert_eq!(variants.len(), 5);
+        assert_eq!(variants[0].video(), Some("chunked"));
+        assert_eq!(variants[0].bandwidth(), 8534030);
+        assert_eq!(
+            variants[0].resolution(),
+            Some(Resolution {
+                width: 1920,
+                height: 1080
+            })
+        );
+        assert_eq!(variants[0].frame_rate(), Some(60.0));
+        assert_eq!(variants[0].codecs(), Some("avc1.64002A,mp4a.40.2"));
+        assert!(variants[4].is_audio_only());
+        assert_eq!(variants[4].resolution(), None);
+        assert_eq!(
+            playlist.media("chunked").and_then(Media::name),
+            Some("1080p60 (source)")
+        );
+    }
+
+    #[test]
+    fn master_retain_variants() {
+        let mut playlist = parse_master(MASTER);
+        playlist.retain_variants(|variant| variant.video() == Some("720p60"));
+
+        assert_eq!(playlist.variants().count(), 1);
+        assert!(playlist.media("720p60").is_some());
+        assert!(playlist.media("chunked").is_none());
+        assert!(playlist.to_string().contains("#EXT-X-TWITCH-INFO:"));
+    }
+
+    #[test]
+    fn master_keeps_tags_before_variant_uri() {
+        let text = "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=160000,VIDEO=\"audio_only\"\n#EXT-X-CUSTOM:1\naudio_only.m3u8\n";
+        let playlist = parse_master(text);
+
+        let variant = playlist.variants().next().unwrap();
+        assert_eq!(variant.tags, ["#EXT-X-CUSTOM:1"]);
+        assert_eq!(variant.uri, "audio_only.m3u8");
+        assert_eq!(playlist.to_string(), text);
+    }
+
+    #[test]
+    fn master_variant_without_uri() {
+        assert!(
+            Playlist::parse("#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1\n#EXT-X-CUSTOM\n").is_err()
+        );
+    }
+
+    #[test]
+    fn live_round_trip() {
+        assert_eq!(parse_media(LIVE).to_string(), LIVE);
+    }
+
+    #[test]
+    fn vod_round_trip() {
+        assert_eq!(parse_media(VOD).to_string(), VOD);
+    }
+
+    #[test]
+    fn live_segments() {
+        let playlist = parse_media(LIVE);
+
+        assert_eq!(playlist.media_sequence(), 4417);
+        assert_eq!(playlist.discontinuity_sequence(), 0);
+        assert_eq!(playlist.segments.len(), 5);
+        assert_eq!(playlist.prefetch.len(), 2);
+        assert!(!playlist.end_list);
+
+        let first = &playlist.segments[0];
+        assert_eq!(first.duration(), 2.002);
+        assert_eq!(first.title(), "live");
+        assert_eq!(first.program_date_time(), Some("2023-10-11T18:28:34.123Z"));
+        assert_eq!(first.date_ranges().count(), 2);
+        assert!(!first.is_discontinuity());
+
+        let ad = &playlist.segments[2];
+        assert!(ad.is_discontinuity());
+        assert_eq!(ad.title(), "Amazon|5432109876");
+        assert_eq!(
+            ad.date_ranges().next().and_then(DateRange::class),
+            Some("twitch-stitched-ad")
+        );
+
+        // The quartile comes after the program date time and stays there
+        assert!(matches!(
+            playlist.segments[3].tags[..],
+            [
+                SegmentTag::ProgramDateTime(_),
+                SegmentTag::DateRange(_),
+                SegmentTag::Info { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn vod_segments() {
+        let playlist = parse_media(VOD);
+
+        assert!(playlist.end_list);
+        assert_eq!(playlist.segments.len(), 4);
+        assert_eq!(playlist.segments[3].duration(), 1.336);
+        assert_eq!(playlist.segments[3].title(), "");
+        assert!(playlist
+            .tags
+            .iter()
+            .any(|tag| tag.starts_with("#ID3-EQUIV-TDTG")));
+    }
+
+    #[test]
+    fn media_keeps_duration_as_written() {
+        let text = "#EXTM3U\n#EXTINF:2.0000001,live\n0.ts\n";
+        let playlist = parse_media(text);
+
+        assert_eq!(playlist.to_string(), text);
+        assert_eq!(playlist.segments[0].duration_text(), "2.0000001");
+    }
+
+    #[test]
+    fn media_keeps_trailing_tags() {
+        let text = "#EXTM3U\n#EXTINF:2.000,live\n0.ts\n#EXT-X-DISCONTINUITY\n#EXT-X-TWITCH-PREFETCH:1.ts\n";
+        let playlist = parse_media(text);
+
+        assert!(matches!(
+            playlist.trailing_tags[..],
+            [SegmentTag::Discontinuity]
+        ));
+        assert_eq!(playlist.to_string(), text);
+    }
+
+    #[test]
+    fn media_set_sequences() {
+        let mut playlist = parse_media(LIVE);
+        playlist.set_media_sequence(4419);
+        playlist.set_discontinuity_sequence(1);
+
+        assert_eq!(playlist.media_sequence(), 4419);
+        assert_eq!(playlist.discontinuity_sequence(), 1);
+        assert_eq!(
+            playlist.tags[..4],
+            [
+                "#EXT-X-VERSION:3",
+                "#EXT-X-TARGETDURATION:6",
+                "#EXT-X-MEDIA-SEQUENCE:4419",
+                "#EXT-X-DISCONTINUITY-SEQUENCE:1"
+            ]
+        );
+    }
+
+    #[test]
+    fn segment_edits() {
+        let mut segment = parse_media(LIVE).segments.remove(2);
+        segment.set_title("live");
+        segment.retain_date_ranges(|_| false);
+        segment.set_discontinuity();
+
+        assert_eq!(segment.title(), "live");
+        assert_eq!(segment.date_ranges().count(), 0);
+        assert_eq!(
+            segment
+                .tags
+                .iter()
+                .filter(|tag| matches!(tag, SegmentTag::Discontinuity))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn invalid_playlists() {
+        assert!(Playlist::parse("#EXT-X-VERSION:3\n").is_err());
+        assert!(Playlist::parse("#EXTM3U\n0.ts\n").is_err());
+        assert!(Playlist::parse("#EXTM3U\n#EXTINF:abc,live\n0.ts\n").is_err());
+    }
+}
@@ -1,6 +1,5 @@
 use std::time::Duration;
 
-use anyhow::{anyhow, Context, Result};
 use lazy_static::lazy_static;
 use serde::{de::DeserializeOwned, Serialize};
 use tauri_plugin_http::reqwest::{
@@ -9,6 +8,8 @@ use tauri_plugin_http::reqwest::{
     Client,
 };
 
+use super::query::{GraphQLError, GraphQLQuery, GraphQLResponse, GraphQLResponseError};
+
 const GRAPHQL_API: &str = "https://gql.twitch.tv/gql";
 
 const CLIENT_ID: &str = "kimne78kx3ncx6brgo4mv6wki5h1ko";
@@ -40,31 +41,71 @@ lazy_static! {
        .unwrap();
 }
 
-pub async fn send_query<RequestJson, ResponseJson>(body: RequestJson) -> Result<ResponseJson>
+/// Sends a query and returns its data, GraphQL errors in the response are returned as [`GraphQLError::Query`].
+pub async fn send_query<Variables, Data>(
+    query: GraphQLQuery<Variables>,
+) -> Result<Data, GraphQLError>
+where
+    Variables: Serialize,
+    Data: DeserializeOwned,
+{
+    let response: GraphQLResponse<Data> = post(&query).await?;
+    response.into_data()
+}
+
+/// Sends a query whose data is still useful if some of its fields fail, returns the data along with the errors.
+pub async fn send_query_partial<Variables, Data>(
+    query: GraphQLQuery<Variables>,
+) -> Result<(Data, Vec<GraphQLResponseError>), GraphQLError>
+where
+    Variables: Serialize,
+    Data: DeserializeOwned,
+{
+    let response: GraphQLResponse<Data> = post(&query).await?;
+    response.into_partial_data()
+}
+
+/// Sends multiple queries in a single request, each one succeeds or fails on its own.
+///
+/// Only fails as a whole if the request itself fails.
+pub async fn send_batch<Variables, Data>(
+    queries: Vec<GraphQLQuery<Variables>>,
+) -> Result<Vec<Result<Data, GraphQLError>>, GraphQLError>
+where
+    Variables: Serialize,
+    Data: DeserializeOwned,
+{
+    let responses: Vec<GraphQLResponse<Data>> = post(&queries).await?;
+
+    let mut results: Vec<Result<Data, GraphQLError>> = responses
+        .into_iter()
+        .map(GraphQLResponse::into_data)
+        .collect();
+
+    // Responses are matched to queries by position, missing ones fail on their own
+    results.resize_with(queries.len(), || Err(GraphQLError::MissingData));
+
+    Ok(results)
+}
+
+async fn post<RequestJson, ResponseJson>(body: &RequestJson) -> Result<ResponseJson, GraphQLError>
 where
     RequestJson: Serialize,
     ResponseJson: DeserializeOwned,
 {
     let response = HTTP_CLIENT
         .post(GRAPHQL_API)
-        .json(&body)
+        .json(body)
         .send()
         .await
-        .context("Failed to send GraphQL request")?;
+        .map_err(GraphQLError::Request)?;
 
     let status = response.status();
 
     if !status.is_success() {
-        let error_body = response
-            .text()
-            .await
-            .context("Failed to read GraphQL response")?;
-
-        return Err(anyhow!("GraphQL request failed: {status} - {error_body}"));
+        let error_body = response.text().await.map_err(GraphQLError::Request)?;
+        return Err(GraphQLError::Status(status, error_body));
     }
 
-    response
-        .json()
-        .await
-        .context("Failed to deserialize GraphQL response")
+    response.json().await.map_err(GraphQLError::Request)
 }
@@ -1,7 +1,21 @@
+pub mod auto_record;
 pub mod chat;
+pub mod clip;
+pub mod directory;
+pub mod dvr;
 pub mod emote;
+pub mod m3u8;
 pub mod main;
+pub mod notification;
+pub mod profile;
 pub mod proxy;
+pub mod quality;
 pub mod query;
+pub mod recorder;
+pub mod search;
+pub mod server;
+pub mod session;
+pub mod stats;
 pub mod stream;
 pub mod user;
+pub mod vod;
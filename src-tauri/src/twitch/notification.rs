@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use log::error;
+use serde::Serialize;
+use sqlx::{Pool, Row, Sqlite};
+use tauri::{async_runtime::Mutex, AppHandle, Emitter, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::AppState;
+
+use super::stream::LiveNow;
+
+/// Sent to every window with the `went_live` event.
+#[derive(Serialize, Clone)]
+pub struct WentLive {
+    pub username: String,
+    pub title: String,
+    pub game: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct NotificationSetting {
+    pub username: String,
+    pub enabled: bool,
+}
+
+/// Returns if go live notifications are enabled for every Twitch user, they are enabled by default.
+#[tauri::command]
+pub async fn get_notification_settings(
+    state: State<'_, Mutex<AppState>>,
+) -> Result<Vec<NotificationSetting>, String> {
+    let state = state.lock().await;
+    let users_db = state.users_db.as_ref().unwrap();
+
+    let query = "SELECT twitch.username, twitch_notifications.enabled FROM twitch LEFT JOIN twitch_notifications ON twitch.username = twitch_notifications.username";
+
+    let rows = match sqlx::query(query).fetch_all(users_db).await {
+        Ok(rows) => rows,
+        Err(err) => {
+            return Err(format!("Failed to fetch notification settings: {err}"));
+        }
+    };
+
+    let mut settings: Vec<NotificationSetting> = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let enabled: Option<bool> = row.try_get("enabled").map_err(|e| e.to_string())?;
+
+        settings.push(NotificationSetting {
+            username: row.try_get("username").map_err(|e| e.to_string())?,
+            enabled: enabled.unwrap_or(true),
+        });
+    }
+
+    Ok(settings)
+}
+
+#[tauri::command]
+pub async fn set_notifications(
+    state: State<'_, Mutex<AppState>>,
+    username: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let state = state.lock().await;
+    let users_db = state.users_db.as_ref().unwrap();
+
+    let query = "INSERT INTO twitch_notifications (username, enabled) VALUES (?, ?) ON CONFLICT (username) DO UPDATE SET enabled = ?";
+
+    if let Err(err) = sqlx::query(query)
+        .bind(&username)
+        .bind(enabled)
+        .bind(enabled)
+        .execute(users_db)
+        .await
+    {
+        return Err(format!("Failed to save notification setting: {err}"));
+    }
+
+    Ok(())
+}
+
+/// Emits `went_live` for every user that went live since the last feed refresh,
+/// and shows a desktop notification for the ones with notifications enabled.
+pub async fn emit_went_live(
+    app_handle: &AppHandle,
+    users_db: &Pool<Sqlite>,
+    went_live: Vec<&LiveNow>,
+) -> Result<()> {
+    for live_now in went_live {
+        let query = "SELECT twitch.avatar, twitch_notifications.enabled FROM twitch LEFT JOIN twitch_notifications ON twitch.username = twitch_notifications.username WHERE twitch.username = ?";
+
+        let row = sqlx::query(query)
+            .bind(&live_now.username)
+            .fetch_optional(users_db)
+            .await?;
+
+        let (avatar, enabled) = match row {
+            Some(row) => (
+                row.try_get::<Option<Vec<u8>>, _>("avatar")?,
+                row.try_get::<Option<bool>, _>("enabled")?,
+            ),
+            None => (None, None),
+        };
+
+        if enabled.unwrap_or(true) {
+            let body = match &live_now.game {
+                Some(game) => format!("{}\n{game}", live_now.title),
+                None => live_now.title.clone(),
+            };
+
+            let mut notification = app_handle
+                .notification()
+                .builder()
+                .title(format!("{} is live", live_now.username))
+                .body(body);
+
+            // Without an avatar the app icon is shown
+            match cache_avatar(app_handle, &live_now.username, avatar.unwrap_or_default()).await {
+                Ok(Some(path)) => notification = notification.icon(path.to_string_lossy()),
+                Ok(None) => {}
+                Err(err) => error!("Failed to cache avatar of '{}': {err}", live_now.username),
+            }
+
+            if let Err(err) = notification.show() {
+                error!("Failed to show notification: {err}");
+            }
+        }
+
+        let event = WentLive {
+            username: live_now.username.clone(),
+            title: live_now.title.clone(),
+            game: live_now.game.clone(),
+        };
+
+        if let Err(err) = app_handle.emit("went_live", event) {
+            error!("Failed to emit 'went_live' event: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the avatar to the cache directory, notifications only take icons from files.
+async fn cache_avatar(
+    app_handle: &AppHandle,
+    username: &str,
+    avatar: Vec<u8>,
+) -> Result<Option<PathBuf>> {
+    if avatar.is_empty() {
+        return Ok(None);
+    }
+
+    let directory = app_handle.path().app_cache_dir()?.join("avatars");
+    tokio::fs::create_dir_all(&directory).await?;
+
+    // Twitch usernames only have letters, numbers and underscores
+    let path = directory.join(username);
+    tokio::fs::write(&path, avatar).await?;
+
+    Ok(Some(path))
+}
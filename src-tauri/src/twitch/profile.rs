@@ -0,0 +1,228 @@
+use log::warn;
+use serde::Serialize;
+
+use super::{
+    main,
+    query::{ChannelProfileData, GraphQLQuery},
+};
+
+/// Latest past broadcasts returned with the profile.
+const PROFILE_VIDEOS: u32 = 12;
+
+/// Most viewed clips of the last month returned with the profile.
+const PROFILE_CLIPS: u32 = 12;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelProfile {
+    pub username: String,
+    pub display_name: String,
+    pub description: String,
+    /// URL of the profile picture.
+    pub avatar: String,
+    pub banner: Option<String>,
+    pub followers: i64,
+    pub socials: Vec<SocialLink>,
+    pub panels: Vec<Panel>,
+    /// Upcoming streams, cancelled ones included so they can be shown as such.
+    pub schedule: Vec<ScheduleSegment>,
+    /// Latest past broadcasts, newest first.
+    pub videos: Vec<ChannelVideo>,
+    /// Most viewed clips of the last month.
+    pub clips: Vec<ChannelClip>,
+    /// Parts of the profile that failed to load, e.g. the schedule, the rest of the profile is still returned.
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct SocialLink {
+    /// Platform, e.g. `youtube`.
+    pub name: String,
+    pub title: String,
+    pub url: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Panel {
+    pub id: String,
+    pub title: Option<String>,
+    /// Markdown.
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub link: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleSegment {
+    pub id: String,
+    pub start_at: String,
+    pub end_at: Option<String>,
+    pub title: Option<String>,
+    pub categories: Vec<String>,
+    pub cancelled: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelVideo {
+    /// Used to watch it with `vod_proxy_url`.
+    pub id: String,
+    pub title: String,
+    pub created_at: String,
+    /// Duration in seconds.
+    pub duration: u64,
+    pub views: i64,
+    pub thumbnail: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelClip {
+    /// Used to watch it with `fetch_clip`.
+    pub slug: String,
+    pub title: String,
+    pub created_at: String,
+    /// Duration in seconds.
+    pub duration: u64,
+    pub views: i64,
+    pub thumbnail: String,
+}
+
+/// Returns everything shown in a channel's page besides its stream.
+#[tauri::command]
+pub async fn fetch_channel_profile(username: &str) -> Result<ChannelProfile, String> {
+    if username.is_empty() {
+        return Err(String::from("No username provided"));
+    }
+
+    let gql = GraphQLQuery::channel_profile(username, PROFILE_VIDEOS, PROFILE_CLIPS);
+
+    let (response, errors): (ChannelProfileData, _) = match main::send_query_partial(gql).await {
+        Ok(response) => response,
+        Err(err) => {
+            return Err(format!("Failed to fetch profile of '{username}': {err}"));
+        }
+    };
+
+    let errors: Vec<String> = errors.into_iter().map(|err| err.message).collect();
+
+    if !errors.is_empty() {
+        warn!(
+            "Profile of '{username}' is incomplete: {}",
+            errors.join("; ")
+        );
+    }
+
+    let Some(user) = response.user else {
+        return Err(format!("User '{username}' not found"));
+    };
+
+    let (socials, segments) = match user.channel {
+        Some(channel) => (
+            channel.social_medias.unwrap_or_default(),
+            channel
+                .schedule
+                .and_then(|schedule| schedule.segments)
+                .unwrap_or_default(),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let socials = socials
+        .into_iter()
+        .map(|social| SocialLink {
+            name: social.name,
+            title: social.title,
+            url: social.url,
+        })
+        .collect();
+
+    let schedule = segments
+        .into_iter()
+        .map(|segment| ScheduleSegment {
+            id: segment.id,
+            start_at: segment.start_at,
+            end_at: segment.end_at,
+            title: segment.title,
+            categories: segment
+                .categories
+                .unwrap_or_default()
+                .into_iter()
+                .map(|category| category.name)
+                .collect(),
+            cancelled: segment.is_cancelled.unwrap_or_default(),
+        })
+        .collect();
+
+    let panels = user
+        .panels
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|panel| panel.kind == "DEFAULT")
+        .map(|panel| Panel {
+            id: panel.id,
+            title: panel.title.filter(|title| !title.is_empty()),
+            description: panel.description.filter(|text| !text.is_empty()),
+            image: panel.image_url.filter(|url| !url.is_empty()),
+            link: panel.link_url.filter(|url| !url.is_empty()),
+        })
+        .collect();
+
+    let videos = user
+        .videos
+        .map(|videos| videos.edges)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|edge| {
+            let video = edge.node;
+
+            ChannelVideo {
+                id: video.id,
+                title: video.title.unwrap_or_default(),
+                created_at: video.created_at,
+                duration: video.length_seconds.unwrap_or_default(),
+                views: video.view_count.unwrap_or_default(),
+                thumbnail: video.preview_thumbnail_url.unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    let clips = user
+        .clips
+        .map(|clips| clips.edges)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|edge| {
+            let clip = edge.node;
+
+            ChannelClip {
+                slug: clip.slug,
+                title: clip.title,
+                created_at: clip.created_at,
+                duration: clip.duration_seconds,
+                views: clip.view_count.unwrap_or_default(),
+                thumbnail: clip.thumbnail_url.unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    Ok(ChannelProfile {
+        display_name: user.display_name,
+        username: user.login,
+        description: user.description.unwrap_or_default(),
+        avatar: user.profile_image_url.unwrap_or_default(),
+        banner: user.banner_image_url,
+        followers: user
+            .followers
+            .map(|followers| followers.total_count)
+            .unwrap_or_default(),
+        socials,
+        panels,
+        schedule,
+        videos,
+        clips,
+        errors,
+    })
+}
@@ -1,205 +1,909 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use anyhow::{anyhow, Result};
+use hyper::body::Bytes;
 use lazy_static::lazy_static;
-use log::error;
-use tauri::{async_runtime::Mutex, AppHandle, Emitter, EventTarget};
+use log::{error, warn};
+use serde::Serialize;
+use tauri::{
+    async_runtime::{self, Mutex},
+    AppHandle, Emitter, EventTarget, Manager,
+};
 
-use super::{main::PROXY_HTTP_CLIENT, stream};
+use crate::{util, AppState};
+
+use super::{
+    dvr,
+    m3u8::{DateRange, MasterPlaylist, MediaPlaylist, Playlist, Segment, SegmentTag, Variant},
+    main::PROXY_HTTP_CLIENT,
+    stats::{self, AdBreak},
+    stream,
+};
+
+/// Playlists are refreshed every few seconds, a request taking longer than this is stuck.
+const PLAYLIST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Segments are a few seconds long, a request taking longer than this is stuck.
+const SEGMENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A segment fails after this many failed downloads.
+const SEGMENT_ATTEMPTS: u32 = 3;
+
+const SEGMENT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Seconds without playlist requests after which the stream of an external player is considered closed.
+const STALE_STATE_AGE: i64 = 5 * 60;
+
+/// Window label and username of the stream.
+type StreamKey = (String, String);
 
 lazy_static! {
-    static ref STREAM_STATE: Mutex<HashMap<String, StreamState>> = Mutex::new(HashMap::new());
+    static ref STREAM_STATE: Mutex<HashMap<StreamKey, StreamState>> = Mutex::new(HashMap::new());
 }
 
-#[derive(Clone)]
+#[derive(Default)]
 struct StreamState {
+    /// Unix timestamp of the last playlist request, used to clean up after external players.
+    last_request_at: i64,
     using_backup: bool,
     main_stream_url: Option<String>,
     backup_stream_url: Option<String>,
+    /// Variants of the main master playlist, used to know what quality is playing.
+    main_variants: Vec<Variant>,
+    /// Variant of the media playlist the player is currently requesting.
+    main_variant: Option<Variant>,
+    ad_break: Option<AdBreakState>,
+}
+
+#[derive(Clone)]
+struct AdBreakState {
+    started_at: i64,
+    backup_served: bool,
+    backup_failed: bool,
 }
 
-async fn update_stream_state(window_label: String, new_state: StreamState) {
+/// Sent to the window playing the stream with the `stream` event.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase", tag = "event", content = "data")]
+pub enum StreamEvent {
+    #[serde(rename_all = "camelCase")]
+    AdBreakStarted {
+        username: String,
+        started_at: i64,
+    },
+    AdBreakEnded(AdBreak),
+}
+
+fn stream_key(window_label: &str, username: &str) -> StreamKey {
+    (window_label.to_string(), username.to_string())
+}
+
+/// Updates the state of a stream in place, only if it still exists.
+///
+/// The state is never written back after a request, so requests running at the same time don't overwrite each other,
+/// and requests still running when the window is closed don't bring back its state.
+async fn update_stream_state<T>(
+    key: &StreamKey,
+    update: impl FnOnce(&mut StreamState) -> T,
+) -> Option<T> {
+    STREAM_STATE.lock().await.get_mut(key).map(update)
+}
+
+/// Starts a new state for the stream when its master playlist is loaded.
+///
+/// Any state left in the window from a previous stream is removed, as the window may have been reused for another channel.
+pub async fn reset_stream_state(window_label: &str, username: &str, main_variants: Vec<Variant>) {
+    // Locked first so no playlist is buffered for DVR while the buffers are removed
     let mut stream_state = STREAM_STATE.lock().await;
-    stream_state.insert(window_label, new_state);
+
+    dvr::remove_window_buffers(window_label).await;
+
+    stream_state.retain(|(label, _), _| label != window_label);
+    stream_state.insert(
+        stream_key(window_label, username),
+        StreamState {
+            last_request_at: util::unix_timestamp(),
+            main_variants,
+            ..Default::default()
+        },
+    );
 }
 
-#[tauri::command]
-pub async fn proxy_stream(
-    app_handle: AppHandle,
-    window_label: String,
+/// Removes the state of every stream played in a window, used when it is closed.
+pub async fn remove_window_state(window_label: &str) {
+    let mut stream_state = STREAM_STATE.lock().await;
+
+    dvr::remove_window_buffers(window_label).await;
+
+    stream_state.retain(|(label, _), _| label != window_label);
+}
+
+/// Removes the state and DVR buffers of external players, e.g. `mpv`, that stopped requesting playlists.
+///
+/// Players in the app have their state removed when their window is closed, external players have no window.
+pub async fn remove_stale_states(app_handle: &AppHandle) {
+    let now = util::unix_timestamp();
+    let mut stream_state = STREAM_STATE.lock().await;
+
+    let stale_labels: Vec<String> = stream_state
+        .iter()
+        .filter(|((label, _), state)| {
+            app_handle.get_webview_window(label).is_none()
+                && now - state.last_request_at > STALE_STATE_AGE
+        })
+        .map(|((label, _), _)| label.clone())
+        .collect();
+
+    for label in stale_labels {
+        dvr::remove_window_buffers(&label).await;
+        stream_state.retain(|(state_label, _), _| *state_label != label);
+    }
+}
+
+/// Fetches a media playlist of the stream playing in a window with its ads stripped.
+///
+/// While the main stream is showing ads, the playlist of the backup stream is served instead.
+/// If `dvr` is set, the playlist covers the last minutes of the stream instead of only the live edge.
+pub async fn proxy_media_playlist(
+    app_handle: &AppHandle,
+    window_label: &str,
     username: &str,
     url: &str,
-) -> Result<String, String> {
+    dvr: bool,
+) -> Result<MediaPlaylist> {
     if url.is_empty() {
-        return Err(String::from("No URL provided"));
+        return Err(anyhow!("No URL provided"));
     }
 
-    let response = match PROXY_HTTP_CLIENT.get(url).send().await {
-        Ok(resp) => resp,
-        Err(err) => {
-            return Err(format!("Failed to proxy request: {err}"));
-        }
+    let mut playlist = fetch_media_playlist(url).await?;
+    let ad_detected = strip_ad_segments(&mut playlist);
+
+    let key = stream_key(window_label, username);
+
+    // The stream is over, there is nothing to switch to
+    let served_backup = if playlist.end_list {
+        false
+    } else {
+        switch_streams(
+            app_handle,
+            window_label,
+            username,
+            url,
+            &key,
+            ad_detected,
+            &mut playlist,
+        )
+        .await
     };
 
-    let body_bytes = match response.bytes().await {
-        Ok(bytes) => bytes,
-        Err(err) => {
-            return Err(format!("Failed to read response body: {err}"));
+    prefetch_to_segments(&mut playlist);
+
+    if dvr {
+        let stream_state = STREAM_STATE.lock().await;
+
+        // Held while buffering, so buffers of a closed window are not created again
+        if stream_state.contains_key(&key) {
+            playlist =
+                dvr::buffer_playlist(window_label, username, url, &playlist, served_backup).await;
         }
-    };
+    }
 
-    let (ad_detected, mut playlist, is_master_playlist) =
-        process_m3u8(&String::from_utf8_lossy(&body_bytes));
-
-    let mut stream_state = {
-        let lock = STREAM_STATE.lock().await;
-        let state = lock.get(&window_label).unwrap_or(&StreamState {
-            using_backup: false,
-            main_stream_url: None,
-            backup_stream_url: None,
-        });
-        state.clone()
-    };
+    Ok(playlist)
+}
 
-    if !is_master_playlist {
-        {
-            if stream_state.main_stream_url.is_none() {
-                stream_state.main_stream_url = Some(url.to_string());
-            }
+/// Replaces the playlist with the backup stream while the main stream shows ads, returns if the backup was served.
+async fn switch_streams(
+    app_handle: &AppHandle,
+    window_label: &str,
+    username: &str,
+    url: &str,
+    key: &StreamKey,
+    ad_detected: bool,
+    playlist: &mut MediaPlaylist,
+) -> bool {
+    // The player may change quality at any time, keep track of it to pick a similar backup quality
+    let stream_state = update_stream_state(key, |state| {
+        state.last_request_at = util::unix_timestamp();
+
+        if let Some(variant) = state.main_variants.iter().find(|v| v.uri == url) {
+            state.main_stream_url = Some(url.to_string());
+            state.main_variant = Some(variant.clone());
+        } else if state.main_stream_url.is_none() {
+            state.main_stream_url = Some(url.to_string());
         }
 
-        if ad_detected {
-            // On ad detection, if backup isn’t already enabled, switch to backup
-            if !stream_state.using_backup {
-                if let Err(err) = app_handle.emit_to(
-                    EventTarget::WebviewWindow {
-                        label: window_label.clone(),
-                    },
-                    "stream",
-                    "backup",
-                ) {
-                    error!("Failed to emit event: {err}");
+        (
+            state.using_backup,
+            state.backup_stream_url.clone(),
+            state.main_stream_url.clone(),
+            state.main_variant.clone(),
+        )
+    })
+    .await;
+
+    // The window was closed or switched to another stream while the playlist was loading
+    let Some((using_backup, backup_stream_url, main_stream_url, main_variant)) = stream_state
+    else {
+        return false;
+    };
+
+    let mut served_backup = false;
+
+    if ad_detected {
+        // On ad detection, if backup isn’t already enabled, switch to backup, checked under the lock so
+        // requests of other qualities at the same time don't start the ad break twice
+        if !using_backup {
+            let started_at = util::unix_timestamp();
+
+            let started = update_stream_state(key, |state| {
+                if state.using_backup {
+                    return false;
                 }
 
-                stream_state.using_backup = true;
+                state.using_backup = true;
+                state.ad_break = Some(AdBreakState {
+                    started_at,
+                    backup_served: false,
+                    backup_failed: false,
+                });
+
+                true
+            })
+            .await;
+
+            if started == Some(true) {
+                emit_stream_event(
+                    app_handle,
+                    window_label,
+                    StreamEvent::AdBreakStarted {
+                        username: username.to_string(),
+                        started_at,
+                    },
+                );
             }
+        }
 
-            // Use the cached backup stream URL if available, if not, fetch it once
-            let backup_url = {
-                if let Some(url) = stream_state.backup_stream_url.clone() {
-                    url
-                } else {
-                    let url = fetch_backup_stream_url(username).await.unwrap_or_default();
-                    stream_state.backup_stream_url = Some(url.clone());
-                    url
-                }
-            };
-
-            // Fetch an updated backup manifest from the cached backup URL
-            match fetch_playlist_text(&backup_url).await {
-                Ok(updated_playlist) => playlist = updated_playlist,
-                Err(err) => {
-                    error!("Failed to fetch updated backup manifest: {err}");
-                    playlist.clear();
-                }
+        // Use the cached backup stream URL if available, if not, fetch it once
+        let backup_url = match backup_stream_url {
+            Some(url) => url,
+            None => {
+                let url = fetch_backup_stream_url(username, main_variant.as_ref())
+                    .await
+                    .unwrap_or_default();
+
+                update_stream_state(key, |state| {
+                    state.backup_stream_url = Some(url.clone());
+                })
+                .await;
+
+                url
+            }
+        };
+
+        // Fetch an updated backup manifest from the cached backup URL, the backup stream may have ads as well,
+        // if it fails, the main stream without its ads is served
+        let backup_served = match fetch_media_playlist(&backup_url).await {
+            Ok(mut updated_playlist) => {
+                strip_ad_segments(&mut updated_playlist);
+                *playlist = updated_playlist;
+                true
             }
-        } else if stream_state.using_backup {
-            // If no ad is detected but we are still in backup, switch back to the main stream
-            if let Err(err) = app_handle.emit_to(
-                EventTarget::WebviewWindow {
-                    label: window_label.clone(),
-                },
-                "stream",
-                "main",
-            ) {
-                error!("Failed to emit event: {err}");
+            Err(err) => {
+                error!("Failed to fetch updated backup manifest: {err}");
+                false
             }
+        };
 
-            match fetch_main_stream(username, &mut stream_state).await {
-                Ok(pl) => playlist = pl,
-                Err(err) => {
-                    error!("Failed to fetch main stream: {err}");
-                    playlist.clear();
-                }
+        update_stream_state(key, |state| {
+            if let Some(ad_break) = state.ad_break.as_mut() {
+                ad_break.backup_served |= backup_served;
+                ad_break.backup_failed |= !backup_served;
             }
+        })
+        .await;
+
+        served_backup = backup_served;
+    } else if using_backup {
+        // If no ad is detected but we are still in backup, switch back to the main stream
+        let ad_break = update_stream_state(key, |state| {
+            state.using_backup = false;
+            state.backup_stream_url = None;
+            state.ad_break.take()
+        })
+        .await
+        .flatten();
+
+        // Only the request that switched back ends the ad break
+        if let Some(ad_break) = ad_break {
+            end_ad_break(app_handle, window_label, username, ad_break);
+        }
 
-            stream_state.using_backup = false;
-            stream_state.backup_stream_url = None;
+        match fetch_main_stream(username, main_stream_url.as_deref(), main_variant.as_ref()).await {
+            Ok(main_playlist) => *playlist = main_playlist,
+            Err(err) => {
+                error!("Failed to fetch main stream: {err}");
+            }
         }
     }
 
-    update_stream_state(window_label, stream_state).await;
+    served_backup
+}
 
-    Ok(playlist)
+fn emit_stream_event(app_handle: &AppHandle, window_label: &str, event: StreamEvent) {
+    if let Err(err) = app_handle.emit_to(
+        EventTarget::WebviewWindow {
+            label: window_label.to_string(),
+        },
+        "stream",
+        event,
+    ) {
+        error!("Failed to emit event: {err}");
+    }
 }
 
-fn process_m3u8(playlist: &str) -> (bool, String, bool) {
-    let mut result_lines = Vec::new();
-    let mut ad_detected = false;
-    let mut is_master_playlist = false;
+fn end_ad_break(
+    app_handle: &AppHandle,
+    window_label: &str,
+    username: &str,
+    ad_break_state: AdBreakState,
+) {
+    let ended_at = util::unix_timestamp();
+
+    let ad_break = AdBreak {
+        username: username.to_string(),
+        started_at: ad_break_state.started_at,
+        ended_at,
+        duration: ended_at - ad_break_state.started_at,
+        backup_succeeded: ad_break_state.backup_served && !ad_break_state.backup_failed,
+    };
+
+    emit_stream_event(
+        app_handle,
+        window_label,
+        StreamEvent::AdBreakEnded(ad_break.clone()),
+    );
 
-    for line in playlist.lines() {
-        if line.starts_with("#EXT-X-STREAM-INF") {
-            is_master_playlist = true;
+    // Saved in the background to not hold the playlist response
+    let app_handle = app_handle.clone();
+    async_runtime::spawn(async move {
+        let state = app_handle.state::<Mutex<AppState>>();
+        let feeds_db = state.lock().await.feeds_db.clone().unwrap();
+
+        if let Err(err) = stats::save_ad_break(&feeds_db, &ad_break).await {
+            error!("Failed to save ad break: {err}");
         }
+    });
+}
+
+/// Twitch marks every segment of the actual stream with this title, ads have titles like `Amazon|123456`.
+const LIVE_SEGMENT_TITLE: &str = "live";
+const AD_DATERANGE_CLASSES: [&str; 2] = ["twitch-stitched-ad", "twitch-ad-quartile"];
+
+/// Removes ad segments from a media playlist, returns whether any ad was found.
+///
+/// Ads at the start of the playlist are dropped and the media sequence is moved forward, ads at the end are dropped
+/// and ads in between live segments are replaced with the last live segment, this way the sequence number of every
+/// live segment stays the same between refreshes and the player doesn't skip or replay anything.
+///
+/// Every replacement and the live segment after them start with a discontinuity, the timestamps go back
+/// at each repeated segment and jump forward when the stream resumes.
+fn strip_ad_segments(playlist: &mut MediaPlaylist) -> bool {
+    let ads = find_ad_segments(playlist);
+
+    if !ads.contains(&true) {
+        return false;
+    }
+
+    let leading_ads = ads.iter().take_while(|is_ad| **is_ad).count();
+    let trailing_ads = ads[leading_ads..]
+        .iter()
+        .rev()
+        .take_while(|is_ad| **is_ad)
+        .count();
+
+    let dropped = &playlist.segments[..leading_ads];
+    let dropped_discontinuities = dropped.iter().filter(|s| s.is_discontinuity()).count() as u64;
+
+    // Keep date ranges that are not about ads, e.g. the stream session
+    let kept_date_ranges: Vec<SegmentTag> = dropped
+        .iter()
+        .flat_map(Segment::date_ranges)
+        .filter(|date_range| !is_ad_date_range(date_range))
+        .cloned()
+        .map(SegmentTag::DateRange)
+        .collect();
+
+    let live_count = ads.len() - leading_ads - trailing_ads;
+    let mut segments = Vec::with_capacity(live_count);
+    let mut last_live_uri: Option<String> = None;
+    let mut after_replacement = false;
+
+    for (mut segment, is_ad) in playlist
+        .segments
+        .drain(..)
+        .zip(ads)
+        .skip(leading_ads)
+        .take(live_count)
+    {
+        if !is_ad {
+            if std::mem::take(&mut after_replacement) {
+                segment.set_discontinuity();
+            }
 
-        if line.contains("stitched-ad") {
-            ad_detected = true;
+            last_live_uri = Some(segment.uri.clone());
+            segments.push(segment);
+            continue;
         }
 
-        result_lines.push(line.to_string());
+        // Ads in between live segments always have a live segment before them
+        let Some(uri) = last_live_uri.clone() else {
+            continue;
+        };
+
+        // Keep the duration of the ad so the timeline doesn't change
+        segment.set_title(LIVE_SEGMENT_TITLE);
+        segment.retain_date_ranges(|date_range| !is_ad_date_range(date_range));
+        segment.set_discontinuity();
+        segment.uri = uri;
+        after_replacement = true;
+
+        segments.push(segment);
+    }
+
+    if let Some(first) = segments.first_mut() {
+        first.tags.splice(0..0, kept_date_ranges);
+    }
+
+    playlist.segments = segments;
+    playlist.set_media_sequence(playlist.media_sequence() + leading_ads as u64);
+
+    if dropped_discontinuities > 0 {
+        playlist.set_discontinuity_sequence(
+            playlist.discontinuity_sequence() + dropped_discontinuities,
+        );
+    }
+
+    // Prefetch hints at the end would point to more ads
+    if trailing_ads > 0 {
+        playlist.prefetch.clear();
+    }
+
+    true
+}
+
+/// Turns the `#EXT-X-TWITCH-PREFETCH` hints into regular segments at the end of the playlist.
+///
+/// Prefetch URIs are the next segments of the stream, Twitch serves them while they are still being encoded,
+/// so any player can start loading them early, like the official player does, and stay closer to the live edge.
+/// They keep the same URI and sequence number once they are complete, so nothing is loaded twice on the next refresh.
+/// This is done after switching to the backup stream, so the hints of the playlist actually served are used,
+/// and before buffering for DVR, so they get DVR URIs and are cached like the other segments.
+fn prefetch_to_segments(playlist: &mut MediaPlaylist) {
+    // Twitch doesn't say how long they will be, they are the same as the other segments
+    let Some(duration) = playlist
+        .segments
+        .last()
+        .map(|segment| segment.duration_text().to_string())
+    else {
+        return;
+    };
+
+    for uri in playlist.prefetch.drain(..) {
+        let mut segment = Segment::new(&duration, LIVE_SEGMENT_TITLE, uri);
+
+        // Tags after the last segment apply to the first prefetched one
+        segment
+            .tags
+            .splice(0..0, std::mem::take(&mut playlist.trailing_tags));
+
+        playlist.segments.push(segment);
     }
+}
 
-    (ad_detected, result_lines.join("\n"), is_master_playlist)
+/// Marks every segment that is part of an ad break.
+///
+/// An ad break starts at a segment with an ad date range and ends at the next discontinuity,
+/// segments with a title other than `live` are also considered ads.
+pub fn find_ad_segments(playlist: &MediaPlaylist) -> Vec<bool> {
+    let mut in_ad_break = false;
+    let mut previous_is_ad = false;
+
+    playlist
+        .segments
+        .iter()
+        .map(|segment| {
+            if segment.is_discontinuity() && previous_is_ad {
+                in_ad_break = false;
+            }
+
+            if segment.date_ranges().any(is_ad_date_range) {
+                in_ad_break = true;
+            }
+
+            let is_ad = in_ad_break
+                || (!segment.title().is_empty() && segment.title() != LIVE_SEGMENT_TITLE);
+
+            previous_is_ad = is_ad;
+            is_ad
+        })
+        .collect()
 }
 
-async fn fetch_playlist_text(url: &str) -> Result<String> {
+fn is_ad_date_range(date_range: &DateRange) -> bool {
+    date_range
+        .class()
+        .is_some_and(|class| AD_DATERANGE_CLASSES.contains(&class))
+        || date_range.id().is_some_and(|id| id.contains("stitched-ad"))
+}
+
+pub async fn fetch_playlist_text(url: &str) -> Result<String> {
     let response = PROXY_HTTP_CLIENT
         .get(url)
+        .timeout(PLAYLIST_TIMEOUT)
         .send()
         .await
         .map_err(|err| anyhow!("Failed to fetch: {err}"))?;
 
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow!("Failed to fetch: {status}"));
+    }
+
     response
         .text()
         .await
         .map_err(|err| anyhow!("Failed to read text: {err}"))
 }
 
-async fn fetch_main_stream(username: &str, stream_state: &mut StreamState) -> Result<String> {
-    let Some(main_url) = stream_state.main_stream_url.clone() else {
+/// Downloads a segment, retrying a few times since a single failed segment is a visible gap.
+pub async fn fetch_segment(url: &str) -> Result<Bytes> {
+    let mut attempt = 1;
+
+    loop {
+        let result = async {
+            PROXY_HTTP_CLIENT
+                .get(url)
+                .timeout(SEGMENT_TIMEOUT)
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await
+        }
+        .await;
+
+        match result {
+            Ok(bytes) => return Ok(bytes),
+            Err(err) if attempt < SEGMENT_ATTEMPTS => {
+                warn!("Failed to download segment (attempt {attempt}/{SEGMENT_ATTEMPTS}): {err}");
+                attempt += 1;
+                tokio::time::sleep(SEGMENT_RETRY_DELAY).await;
+            }
+            Err(err) => return Err(anyhow!("Failed to fetch segment: {err}")),
+        }
+    }
+}
+
+/// Fetches the master playlist of a stream, `backup` is the stream served to embedded players.
+pub async fn fetch_master_playlist(username: &str, backup: bool) -> Result<MasterPlaylist> {
+    let url = match stream::fetch_stream_playback(username, backup).await {
+        Ok(url) => url,
+        Err(err) => {
+            return Err(anyhow!("Failed to fetch stream playback: {err}"));
+        }
+    };
+
+    match Playlist::parse(&fetch_playlist_text(&url).await?)? {
+        Playlist::Master(playlist) => Ok(playlist),
+        Playlist::Media(_) => Err(anyhow!("Expected a master playlist, got a media playlist")),
+    }
+}
+
+pub async fn fetch_media_playlist(url: &str) -> Result<MediaPlaylist> {
+    match Playlist::parse(&fetch_playlist_text(url).await?)? {
+        Playlist::Media(playlist) => Ok(playlist),
+        Playlist::Master(_) => Err(anyhow!("Expected a media playlist, got a master playlist")),
+    }
+}
+
+async fn fetch_main_stream(
+    username: &str,
+    main_stream_url: Option<&str>,
+    main_variant: Option<&Variant>,
+) -> Result<MediaPlaylist> {
+    let Some(main_url) = main_stream_url else {
         error!("Main stream URL not found. Falling back to backup stream.");
-        let backup_url = fetch_backup_stream_url(username).await?;
-        return fetch_playlist_text(&backup_url).await;
+        let backup_url = fetch_backup_stream_url(username, main_variant).await?;
+        let mut playlist = fetch_media_playlist(&backup_url).await?;
+        strip_ad_segments(&mut playlist);
+        return Ok(playlist);
     };
 
-    let body = fetch_playlist_text(&main_url).await?;
-    let (ad_detected, processed_playlist, _) = process_m3u8(&body);
+    // The ad break may not be over in every part of the playlist yet, its ads are stripped
+    let mut playlist = fetch_media_playlist(main_url).await?;
+    strip_ad_segments(&mut playlist);
 
-    if ad_detected {
-        stream_state.using_backup = true;
-        return Ok("#EXTM3U\n#EXT-X-ENDLIST\n".to_string());
+    Ok(playlist)
+}
+
+/// Returns the URL of the backup media playlist, picking the variant closest to the quality being played.
+pub async fn fetch_backup_stream_url(
+    username: &str,
+    main_variant: Option<&Variant>,
+) -> Result<String> {
+    let playlist = fetch_master_playlist(username, true).await?;
+
+    // Audio only players can't switch to video, if there is no audio only backup the main stream is served
+    if main_variant.is_some_and(Variant::is_audio_only) {
+        return playlist
+            .variants()
+            .find(|variant| variant.is_audio_only())
+            .map(|variant| variant.uri.clone())
+            .ok_or_else(|| anyhow!("Backup master playlist has no audio only variant"));
     }
 
-    stream_state.using_backup = false;
-    Ok(processed_playlist)
+    let variant = match main_variant {
+        Some(main_variant) => closest_variant(
+            playlist
+                .variants()
+                .filter(|variant| !variant.is_audio_only()),
+            main_variant,
+        ),
+        None => playlist.variants().find(|variant| !variant.is_audio_only()),
+    }
+    .ok_or_else(|| anyhow!("Backup master playlist has no variants"))?;
+
+    Ok(variant.uri.clone())
 }
 
-async fn fetch_backup_stream_url(username: &str) -> Result<String> {
-    let url = match stream::fetch_stream_playback(username, true).await {
-        Ok(url) => url,
-        Err(err) => {
-            return Err(anyhow!("Failed to fetch backup stream: {err}"));
+/// Finds the variant closest to the target by resolution, then frame rate, then bandwidth.
+fn closest_variant<'a>(
+    variants: impl Iterator<Item = &'a Variant>,
+    target: &Variant,
+) -> Option<&'a Variant> {
+    let target_resolution = target.resolution();
+    let target_frame_rate = target.frame_rate().unwrap_or_default();
+    let target_bandwidth = target.bandwidth();
+
+    variants.min_by_key(|variant| {
+        let height_difference = match (variant.resolution(), target_resolution) {
+            (Some(resolution), Some(target)) => resolution.height.abs_diff(target.height),
+            _ => u64::MAX,
+        };
+
+        let frame_rate_difference =
+            (variant.frame_rate().unwrap_or_default() - target_frame_rate).abs() as u64;
+
+        (
+            height_difference,
+            frame_rate_difference,
+            variant.bandwidth().abs_diff(target_bandwidth),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = include_str!("../../tests/fixtures/twitch/master.m3u8");
+    const LIVE: &str = include_str!("../../tests/fixtures/twitch/live.m3u8");
+
+    fn parse_master(text: &str) -> MasterPlaylist {
+        match Playlist::parse(text).unwrap() {
+            Playlist::Master(playlist) => playlist,
+            Playlist::Media(_) => panic!("Expected a master playlist"),
         }
-    };
+    }
+
+    fn parse_media(text: &str) -> MediaPlaylist {
+        match Playlist::parse(text).unwrap() {
+            Playlist::Media(playlist) => playlist,
+            Playlist::Master(_) => panic!("Expected a media playlist"),
+        }
+    }
+
+    fn segment_names(playlist: &MediaPlaylist) -> Vec<&str> {
+        playlist
+            .segments
+            .iter()
+            .map(|segment| segment.uri.rsplit('/').next().unwrap())
+            .collect()
+    }
+
+    fn discontinuities(playlist: &MediaPlaylist) -> Vec<bool> {
+        playlist
+            .segments
+            .iter()
+            .map(Segment::is_discontinuity)
+            .collect()
+    }
+
+    #[test]
+    fn find_ads_by_date_range() {
+        let playlist = parse_media(LIVE);
+
+        assert_eq!(
+            find_ad_segments(&playlist),
+            [false, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn find_ads_until_discontinuity() {
+        let mut playlist = parse_media(LIVE);
+
+        // Only the first ad has a date range, the second one is found through the ad break
+        for segment in &mut playlist.segments {
+            segment.set_title(LIVE_SEGMENT_TITLE);
+        }
+
+        assert_eq!(
+            find_ad_segments(&playlist),
+            [false, false, true, true, false]
+        );
+    }
 
-    let body = fetch_playlist_text(&url).await?;
+    #[test]
+    fn find_ads_by_title() {
+        let mut playlist = parse_media(LIVE);
+        playlist.segments[0].set_title("Amazon|1234");
 
-    let variant_playlist_url = body
-        .lines()
-        .nth(4)
-        .ok_or_else(|| anyhow!("Backup master playlist is malformed."))?
-        .to_string();
+        assert_eq!(
+            find_ad_segments(&playlist),
+            [true, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn strip_without_ads() {
+        let mut playlist = parse_media(LIVE);
+        playlist.segments.truncate(2);
+
+        let text = playlist.to_string();
+
+        assert!(!strip_ad_segments(&mut playlist));
+        assert_eq!(playlist.to_string(), text);
+    }
 
-    Ok(variant_playlist_url)
+    #[test]
+    fn strip_middle_ads() {
+        let mut playlist = parse_media(LIVE);
+
+        assert!(strip_ad_segments(&mut playlist));
+
+        assert_eq!(
+            segment_names(&playlist),
+            [
+                "CpkE4417.ts",
+                "CpkE4418.ts",
+                "CpkE4418.ts",
+                "CpkE4418.ts",
+                "CpkE4419.ts"
+            ]
+        );
+        assert_eq!(discontinuities(&playlist), [false, false, true, true, true]);
+
+        // Replacements keep the duration and program date time of the ads, without the ad date ranges
+        assert_eq!(playlist.segments[3].duration_text(), "2.000");
+        assert_eq!(playlist.segments[3].title(), LIVE_SEGMENT_TITLE);
+        assert_eq!(
+            playlist.segments[3].program_date_time(),
+            Some("2023-10-11T18:28:40.093Z")
+        );
+        assert!(playlist.segments[2..4]
+            .iter()
+            .all(|segment| segment.date_ranges().next().is_none()));
+
+        assert_eq!(playlist.media_sequence(), 4417);
+        assert_eq!(playlist.discontinuity_sequence(), 0);
+        assert_eq!(playlist.prefetch.len(), 2);
+    }
+
+    #[test]
+    fn strip_leading_ads() {
+        let mut playlist = parse_media(LIVE);
+        let live: Vec<Segment> = playlist.segments.drain(..2).collect();
+
+        // Non ad date ranges on dropped ads are kept
+        let session = live[0].date_ranges().cloned().map(SegmentTag::DateRange);
+        playlist.segments[0].tags.splice(0..0, session);
+
+        assert!(strip_ad_segments(&mut playlist));
+
+        assert_eq!(segment_names(&playlist), ["CpkE4419.ts"]);
+        assert_eq!(
+            playlist.segments[0]
+                .date_ranges()
+                .filter_map(DateRange::id)
+                .collect::<Vec<_>>(),
+            [
+                "playlist-creation-1697040000",
+                "playlist-session-1697040000"
+            ]
+        );
+
+        assert_eq!(playlist.media_sequence(), 4419);
+        assert_eq!(playlist.discontinuity_sequence(), 1);
+        assert_eq!(playlist.prefetch.len(), 2);
+    }
+
+    #[test]
+    fn strip_trailing_ads() {
+        let mut playlist = parse_media(LIVE);
+        playlist.segments.truncate(4);
+
+        assert!(strip_ad_segments(&mut playlist));
+
+        assert_eq!(segment_names(&playlist), ["CpkE4417.ts", "CpkE4418.ts"]);
+        assert_eq!(discontinuities(&playlist), [false, false]);
+        assert_eq!(playlist.media_sequence(), 4417);
+        assert_eq!(playlist.discontinuity_sequence(), 0);
+
+        // The prefetched segments would be ads too
+        assert!(playlist.prefetch.is_empty());
+    }
+
+    fn closest<'a>(
+        playlist: &'a MasterPlaylist,
+        target: &str,
+        excluded: &[&str],
+    ) -> Option<&'a str> {
+        let target = playlist
+            .variants()
+            .find(|variant| variant.video() == Some(target))
+            .unwrap();
+
+        closest_variant(
+            playlist
+                .variants()
+                .filter(|variant| !excluded.contains(&variant.video().unwrap())),
+            target,
+        )
+        .and_then(Variant::video)
+    }
+
+    #[test]
+    fn closest_variant_exact_match() {
+        let playlist = parse_master(MASTER);
+
+        assert_eq!(closest(&playlist, "720p60", &[]), Some("720p60"));
+    }
+
+    #[test]
+    fn closest_variant_by_resolution() {
+        let playlist = parse_master(MASTER);
+
+        assert_eq!(closest(&playlist, "chunked", &["chunked"]), Some("720p60"));
+        assert_eq!(closest(&playlist, "720p60", &["720p60"]), Some("480p30"));
+        assert_eq!(
+            closest(&playlist, "160p30", &["160p30", "audio_only"]),
+            Some("480p30")
+        );
+    }
+
+    #[test]
+    fn closest_variant_without_resolution() {
+        let playlist = parse_master(MASTER);
+
+        // Every variant is as far by resolution, the closest by frame rate then bandwidth is used
+        assert_eq!(
+            closest(&playlist, "audio_only", &["audio_only"]),
+            Some("160p30")
+        );
+    }
+
+    #[test]
+    fn closest_variant_empty() {
+        let playlist = parse_master(MASTER);
+
+        assert_eq!(
+            closest(
+                &playlist,
+                "720p60",
+                &["chunked", "720p60", "480p30", "160p30", "audio_only"]
+            ),
+            None
+        );
+    }
 }
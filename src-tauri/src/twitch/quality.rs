@@ -0,0 +1,318 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{Pool, Row, Sqlite};
+use tauri::{async_runtime::Mutex, State};
+
+use crate::{settings, AppState};
+
+use super::{
+    m3u8::{MasterPlaylist, Variant},
+    proxy,
+};
+
+const PREFERRED_QUALITY_SETTING: &str = "twitch_preferred_quality";
+
+/// Qualities that mean the highest one available.
+const BEST_QUALITIES: [&str; 3] = ["best", "source", "chunked"];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamQuality {
+    /// Quality identifier, e.g. `chunked` or `720p60`, used to set the preferred quality.
+    pub id: String,
+    /// Display name, e.g. `1080p60 (source)`.
+    pub name: String,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    pub frame_rate: Option<f64>,
+    pub codecs: Option<String>,
+    pub bandwidth: u64,
+    /// Only audio, for playing the stream in the background with minimal bandwidth.
+    pub audio_only: bool,
+}
+
+/// Returns the qualities the stream is available in, best first.
+#[tauri::command]
+pub async fn get_stream_qualities(username: &str) -> Result<Vec<StreamQuality>, String> {
+    if username.is_empty() {
+        return Err(String::from("No username provided"));
+    }
+
+    let playlist = match proxy::fetch_master_playlist(username, false).await {
+        Ok(playlist) => playlist,
+        Err(err) => {
+            return Err(format!("Failed to fetch master playlist: {err}"));
+        }
+    };
+
+    Ok(stream_qualities(&playlist))
+}
+
+/// Returns the preferred quality of a channel, or the global one if no `username` is provided.
+#[tauri::command]
+pub async fn get_preferred_quality(
+    state: State<'_, Mutex<AppState>>,
+    username: Option<String>,
+) -> Result<Option<String>, String> {
+    let state = state.lock().await;
+    let users_db = state.users_db.as_ref().unwrap();
+
+    let quality = match username {
+        Some(username) => query_channel_quality(users_db, &username).await,
+        None => settings::get_setting(users_db, PREFERRED_QUALITY_SETTING).await,
+    };
+
+    quality.map_err(|err| format!("Failed to fetch preferred quality: {err}"))
+}
+
+/// Saves the preferred quality of a channel, or the global one if no `username` is provided.
+///
+/// The quality is an identifier like `1080p60`, if the stream doesn't have it, the best quality below it is used.
+/// `None` removes the preference, channels then use the global one and the global one shows every quality.
+#[tauri::command]
+pub async fn set_preferred_quality(
+    state: State<'_, Mutex<AppState>>,
+    username: Option<String>,
+    quality: Option<String>,
+) -> Result<(), String> {
+    let state = state.lock().await;
+    let users_db = state.users_db.as_ref().unwrap();
+
+    let Some(username) = username else {
+        return settings::set_setting(users_db, PREFERRED_QUALITY_SETTING, quality.as_deref())
+            .await
+            .map_err(|err| format!("Failed to save preferred quality: {err}"));
+    };
+
+    let result = match quality {
+        Some(quality) => {
+            let query = "INSERT INTO twitch_quality (username, quality) VALUES (?, ?) ON CONFLICT (username) DO UPDATE SET quality = ?";
+
+            sqlx::query(query)
+                .bind(&username)
+                .bind(&quality)
+                .bind(&quality)
+                .execute(users_db)
+                .await
+        }
+        None => {
+            let query = "DELETE FROM twitch_quality WHERE username = ?";
+
+            sqlx::query(query).bind(&username).execute(users_db).await
+        }
+    };
+
+    if let Err(err) = result {
+        return Err(format!("Failed to save preferred quality: {err}"));
+    }
+
+    Ok(())
+}
+
+/// Returns the quality to play for a channel, its own preference first, then the global one.
+pub async fn preferred_quality(db: &Pool<Sqlite>, username: &str) -> Result<Option<String>> {
+    if let Some(quality) = query_channel_quality(db, username).await? {
+        return Ok(Some(quality));
+    }
+
+    settings::get_setting(db, PREFERRED_QUALITY_SETTING).await
+}
+
+pub fn stream_qualities(playlist: &MasterPlaylist) -> Vec<StreamQuality> {
+    playlist
+        .variants()
+        .map(|variant| {
+            let id = variant.video().unwrap_or_default().to_string();
+
+            let name = playlist
+                .media(&id)
+                .and_then(|media| media.name())
+                .unwrap_or(&id)
+                .to_string();
+
+            let resolution = variant.resolution();
+
+            StreamQuality {
+                id,
+                name,
+                width: resolution.map(|resolution| resolution.width),
+                height: resolution.map(|resolution| resolution.height),
+                frame_rate: variant.frame_rate(),
+                codecs: variant.codecs().map(str::to_string),
+                bandwidth: variant.bandwidth(),
+                audio_only: variant.is_audio_only(),
+            }
+        })
+        .collect()
+}
+
+/// Picks the variant for a quality like `1080p60`.
+///
+/// An exact match is used if there is one, otherwise the best variant that doesn't go over its resolution
+/// and frame rate, or the lowest one if they all do. `best`, `source` or no quality picks the best variant,
+/// `audio_only` is only picked when asked for.
+pub fn select_variant<'a>(
+    playlist: &'a MasterPlaylist,
+    quality: Option<&str>,
+) -> Option<&'a Variant> {
+    let best = || {
+        playlist
+            .variants()
+            .filter(|variant| variant.resolution().is_some())
+            .max_by_key(|variant| quality_key(variant))
+            .or_else(|| playlist.variants().next())
+    };
+
+    let Some(quality) = quality.filter(|quality| !BEST_QUALITIES.contains(quality)) else {
+        return best();
+    };
+
+    if let Some(variant) = playlist
+        .variants()
+        .find(|variant| variant.video() == Some(quality))
+    {
+        return Some(variant);
+    }
+
+    let Some((height, frame_rate)) = parse_quality(quality) else {
+        return best();
+    };
+
+    let video_variants: Vec<&Variant> = playlist
+        .variants()
+        .filter(|variant| variant.resolution().is_some())
+        .collect();
+
+    video_variants
+        .iter()
+        .filter(|variant| {
+            variant.resolution().is_some_and(|r| r.height <= height)
+                && variant.frame_rate().unwrap_or(30.0) <= frame_rate + 1.0
+        })
+        .max_by_key(|variant| quality_key(variant))
+        .or_else(|| {
+            video_variants
+                .iter()
+                .min_by_key(|variant| quality_key(variant))
+        })
+        .copied()
+}
+
+/// Parses `720p60` into its height and frame rate, `720p` is 30 fps.
+fn parse_quality(quality: &str) -> Option<(u64, f64)> {
+    let (height, frame_rate) = quality.split_once('p')?;
+
+    let frame_rate = if frame_rate.is_empty() {
+        30.0
+    } else {
+        frame_rate.parse().ok()?
+    };
+
+    Some((height.parse().ok()?, frame_rate))
+}
+
+/// Sorts variants by resolution, then frame rate, then bandwidth.
+fn quality_key(variant: &Variant) -> (u64, u64, u64) {
+    (
+        variant.resolution().map_or(0, |r| r.height),
+        variant.frame_rate().unwrap_or_default().round() as u64,
+        variant.bandwidth(),
+    )
+}
+
+async fn query_channel_quality(db: &Pool<Sqlite>, username: &str) -> Result<Option<String>> {
+    let query = "SELECT quality FROM twitch_quality WHERE username = ?";
+
+    let row = sqlx::query(query).bind(username).fetch_optional(db).await?;
+
+    match row {
+        Some(row) => Ok(row.try_get("quality")?),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::twitch::m3u8::Playlist;
+
+    const MASTER: &str = include_str!("../../tests/fixtures/twitch/master.m3u8");
+
+    fn parse_master(text: &str) -> MasterPlaylist {
+        match Playlist::parse(text).unwrap() {
+            Playlist::Master(playlist) => playlist,
+            Playlist::Media(_) => panic!("Expected a master playlist"),
+        }
+    }
+
+    fn selected<'a>(playlist: &'a MasterPlaylist, quality: Option<&str>) -> Option<&'a str> {
+        select_variant(playlist, quality).and_then(Variant::video)
+    }
+
+    #[test]
+    fn select_best() {
+        let playlist = parse_master(MASTER);
+
+        assert_eq!(selected(&playlist, None), Some("chunked"));
+        assert_eq!(selected(&playlist, Some("best")), Some("chunked"));
+        assert_eq!(selected(&playlist, Some("source")), Some("chunked"));
+        assert_eq!(selected(&playlist, Some("chunked")), Some("chunked"));
+    }
+
+    #[test]
+    fn select_exact_match() {
+        let playlist = parse_master(MASTER);
+
+        assert_eq!(selected(&playlist, Some("720p60")), Some("720p60"));
+        assert_eq!(selected(&playlist, Some("480p30")), Some("480p30"));
+        assert_eq!(selected(&playlist, Some("audio_only")), Some("audio_only"));
+    }
+
+    #[test]
+    fn select_best_below() {
+        let playlist = parse_master(MASTER);
+
+        assert_eq!(selected(&playlist, Some("900p60")), Some("720p60"));
+        // 720p60 goes over the frame rate
+        assert_eq!(selected(&playlist, Some("720p30")), Some("480p30"));
+        assert_eq!(selected(&playlist, Some("1080p")), Some("480p30"));
+        assert_eq!(selected(&playlist, Some("1440p60")), Some("chunked"));
+    }
+
+    #[test]
+    fn select_lowest_if_all_above() {
+        let playlist = parse_master(MASTER);
+
+        // Audio only is never picked as the lowest quality
+        assert_eq!(selected(&playlist, Some("144p")), Some("160p30"));
+        assert_eq!(selected(&playlist, Some("144p60")), Some("160p30"));
+    }
+
+    #[test]
+    fn select_unknown_quality() {
+        let playlist = parse_master(MASTER);
+
+        assert_eq!(selected(&playlist, Some("high")), Some("chunked"));
+    }
+
+    #[test]
+    fn select_audio_only_playlist() {
+        let mut playlist = parse_master(MASTER);
+        playlist.retain_variants(Variant::is_audio_only);
+
+        assert_eq!(selected(&playlist, None), Some("audio_only"));
+        assert_eq!(selected(&playlist, Some("720p60")), None);
+    }
+
+    #[test]
+    fn parse_qualities() {
+        assert_eq!(parse_quality("1080p60"), Some((1080, 60.0)));
+        assert_eq!(parse_quality("720p"), Some((720, 30.0)));
+        assert_eq!(parse_quality("160p30"), Some((160, 30.0)));
+        assert_eq!(parse_quality("audio_only"), None);
+        assert_eq!(parse_quality("p60"), None);
+        assert_eq!(parse_quality("720pabc"), None);
+        assert_eq!(parse_quality(""), None);
+    }
+}
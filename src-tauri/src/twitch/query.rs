@@ -1,7 +1,7 @@
-use serde::{Deserialize, Serialize};
+use std::fmt;
 
-const USE_LIVE_QUERY_HASH: &str =
-    "639d5f11bfb8bf3053b424d9ef650d04c4ebb7d94711d644afb08fe9a0fad5d9";
+use serde::{Deserialize, Serialize};
+use tauri_plugin_http::reqwest::{self, StatusCode};
 
 /// Main query struct used throughout the app.
 ///
@@ -9,91 +9,808 @@ const USE_LIVE_QUERY_HASH: &str =
 /// would outweigh the benefits of the using them (less overhead when parsing on the server side).
 ///
 /// For now, until a better method is found, I will be doing it this way.
+///
+/// Query texts are constant, anything coming from the user or the app is sent in `variables`,
+/// so a login with quotes or braces can't break or change the query.
 #[derive(Serialize)]
-pub struct GraphQLQuery {
-    query: String,
+pub struct GraphQLQuery<Variables> {
+    query: &'static str,
+    variables: Variables,
 }
 
-impl GraphQLQuery {
+impl GraphQLQuery<LoginVariables> {
     pub fn full_user(username: &str) -> Self {
-        let gql = format!(
-            r#"{{
-                user(login: "{username}") {{
+        const QUERY: &str = r"
+            query FullUser($login: String!) {
+                user(login: $login) {
                     id
                     profileImageURL(width: 50)
-                    stream {{
+                    subscriptionProducts {
+                        emotes {
+                            id
+                            token
+                        }
+                    }
+                }
+            }
+        ";
+
+        Self {
+            query: QUERY,
+            variables: LoginVariables::new(username),
+        }
+    }
+
+    /// Used when refreshing the feed, `stream` is null if the user is offline.
+    pub fn live_now(username: &str) -> Self {
+        const QUERY: &str = r"
+            query LiveNow($login: String!) {
+                user(login: $login) {
+                    login
+                    stream {
+                        createdAt
                         title
                         viewersCount
-                        createdAt
-                        game {{
-                            id
+                        previewImageURL(width: 440, height: 248)
+                        game {
+                            name
+                        }
+                        freeformTags {
+                            name
+                        }
+                    }
+                }
+            }
+        ";
+
+        Self {
+            query: QUERY,
+            variables: LoginVariables::new(username),
+        }
+    }
+}
+
+impl GraphQLQuery<ChannelProfileVariables> {
+    /// Used for the channel page, everything about a channel besides its stream.
+    pub fn channel_profile(username: &str, videos: u32, clips: u32) -> Self {
+        const QUERY: &str = r"
+            query ChannelProfile($login: String!, $videos: Int!, $clips: Int!) {
+                user(login: $login) {
+                    login
+                    displayName
+                    description
+                    profileImageURL(width: 150)
+                    bannerImageURL
+                    followers {
+                        totalCount
+                    }
+                    channel {
+                        socialMedias {
                             name
-                        }}
-                    }}
-                    subscriptionProducts {{
-                        emotes {{
+                            title
+                            url
+                        }
+                        schedule {
+                            segments {
+                                id
+                                startAt
+                                endAt
+                                title
+                                isCancelled
+                                categories {
+                                    name
+                                }
+                            }
+                        }
+                    }
+                    panels {
+                        id
+                        type
+                        ... on DefaultPanel {
+                            title
+                            description
+                            imageURL
+                            linkURL
+                        }
+                    }
+                    videos(first: $videos, type: ARCHIVE, sort: TIME) {
+                        edges {
+                            node {
+                                id
+                                title
+                                createdAt
+                                lengthSeconds
+                                viewCount
+                                previewThumbnailURL(width: 320, height: 180)
+                            }
+                        }
+                    }
+                    clips(first: $clips, criteria: { period: LAST_MONTH, sort: VIEWS_DESC }) {
+                        edges {
+                            node {
+                                slug
+                                title
+                                createdAt
+                                durationSeconds
+                                viewCount
+                                thumbnailURL
+                            }
+                        }
+                    }
+                }
+            }
+        ";
+
+        Self {
+            query: QUERY,
+            variables: ChannelProfileVariables {
+                login: username.to_string(),
+                videos,
+                clips,
+            },
+        }
+    }
+}
+
+impl GraphQLQuery<UserPageVariables> {
+    /// Used to import the channels a user follows, a page at a time.
+    pub fn follows(username: &str, first: u32, after: Option<&str>) -> Self {
+        const QUERY: &str = r"
+            query Follows($login: String!, $first: Int!, $after: Cursor) {
+                user(login: $login) {
+                    follows(first: $first, after: $after) {
+                        edges {
+                            cursor
+                            node {
+                                login
+                            }
+                        }
+                        pageInfo {
+                            hasNextPage
+                        }
+                    }
+                }
+            }
+        ";
+
+        Self {
+            query: QUERY,
+            variables: UserPageVariables {
+                login: username.to_string(),
+                first,
+                after: after.map(str::to_string),
+            },
+        }
+    }
+
+    /// Used to retrieve the latest past broadcasts of a user.
+    pub fn archive_videos(username: &str, first: u32) -> Self {
+        const QUERY: &str = r"
+            query ArchiveVideos($login: String!, $first: Int!, $after: Cursor) {
+                user(login: $login) {
+                    videos(first: $first, after: $after, type: ARCHIVE, sort: TIME) {
+                        edges {
+                            node {
+                                id
+                                createdAt
+                            }
+                        }
+                    }
+                }
+            }
+        ";
+
+        Self {
+            query: QUERY,
+            variables: UserPageVariables {
+                login: username.to_string(),
+                first,
+                after: None,
+            },
+        }
+    }
+}
+
+impl GraphQLQuery<SearchVariables> {
+    /// Used to find channels by their name, best matches first.
+    pub fn search_channels(query: &str, first: u32) -> Self {
+        const QUERY: &str = r"
+            query SearchChannels($query: String!, $first: Int!) {
+                searchUsers(userQuery: $query, first: $first) {
+                    edges {
+                        node {
+                            login
+                            displayName
+                            profileImageURL(width: 50)
+                            followers {
+                                totalCount
+                            }
+                            stream {
+                                viewersCount
+                            }
+                        }
+                    }
+                }
+            }
+        ";
+
+        Self {
+            query: QUERY,
+            variables: SearchVariables {
+                query: query.to_string(),
+                first,
+            },
+        }
+    }
+}
+
+impl GraphQLQuery<PageVariables> {
+    /// Used to browse the categories with the most viewers.
+    pub fn top_categories(first: u32, after: Option<&str>) -> Self {
+        const QUERY: &str = r"
+            query TopCategories($first: Int!, $after: Cursor) {
+                games(first: $first, after: $after) {
+                    edges {
+                        cursor
+                        node {
                             id
-                            token
-                        }}
-                    }}
-                }}
-            }}"#
-        );
+                            name
+                            displayName
+                            viewersCount
+                            boxArtURL(width: 188, height: 250)
+                        }
+                    }
+                    pageInfo {
+                        hasNextPage
+                    }
+                }
+            }
+        ";
+
+        Self {
+            query: QUERY,
+            variables: PageVariables {
+                first,
+                after: after.map(str::to_string),
+            },
+        }
+    }
+}
+
+impl GraphQLQuery<CategoryStreamsVariables> {
+    /// Used to browse the live streams of a category, most viewers first.
+    pub fn category_streams(
+        category: &str,
+        first: u32,
+        after: Option<&str>,
+        languages: &[String],
+    ) -> Self {
+        const QUERY: &str = r"
+            query CategoryStreams($name: String!, $first: Int!, $after: Cursor, $options: GameStreamOptions) {
+                game(name: $name) {
+                    name
+                    streams(first: $first, after: $after, options: $options) {
+                        edges {
+                            cursor
+                            node {
+                                createdAt
+                                title
+                                viewersCount
+                                previewImageURL(width: 440, height: 248)
+                                game {
+                                    name
+                                }
+                                freeformTags {
+                                    name
+                                }
+                                broadcaster {
+                                    login
+                                }
+                            }
+                        }
+                        pageInfo {
+                            hasNextPage
+                        }
+                    }
+                }
+            }
+        ";
+
+        Self {
+            query: QUERY,
+            variables: CategoryStreamsVariables {
+                name: category.to_string(),
+                first,
+                after: after.map(str::to_string),
+                options: StreamOptions::new(languages),
+            },
+        }
+    }
+}
+
+impl GraphQLQuery<StreamsVariables> {
+    /// Used to browse the live streams with the most viewers across every category.
+    pub fn top_streams(first: u32, after: Option<&str>, languages: &[String]) -> Self {
+        const QUERY: &str = r"
+            query TopStreams($first: Int!, $after: Cursor, $options: StreamOptions) {
+                streams(first: $first, after: $after, options: $options) {
+                    edges {
+                        cursor
+                        node {
+                            createdAt
+                            title
+                            viewersCount
+                            previewImageURL(width: 440, height: 248)
+                            game {
+                                name
+                            }
+                            freeformTags {
+                                name
+                            }
+                            broadcaster {
+                                login
+                            }
+                        }
+                    }
+                    pageInfo {
+                        hasNextPage
+                    }
+                }
+            }
+        ";
 
         Self {
-            query: gql.trim().to_string(),
+            query: QUERY,
+            variables: StreamsVariables {
+                first,
+                after: after.map(str::to_string),
+                options: StreamOptions::new(languages),
+            },
         }
     }
+}
 
+impl GraphQLQuery<StreamPlaybackVariables> {
     /// Used to retrieve playback access token for a stream.
     pub fn playback_query(username: &str, backup_stream: bool) -> Self {
-        let platform = if backup_stream { "ios" } else { "web" };
-        let player_type = if backup_stream { "autoplay" } else { "site" };
-
-        let gql = format!(
-            r#"{{
-                streamPlaybackAccessToken(
-                    channelName: "{username}",
-                    params: {{
-                        platform: "{platform}",
-                        playerBackend: "mediaplayer",
-                        playerType: "{player_type}",
-                    }}
-                )
-                {{
+        const QUERY: &str = r"
+            query StreamPlayback($login: String!, $params: PlaybackAccessTokenParams!) {
+                streamPlaybackAccessToken(channelName: $login, params: $params) {
                     value
                     signature
-                }}
-            }}"#
-        );
+                }
+            }
+        ";
+
+        let params = if backup_stream {
+            PlaybackAccessTokenParams::new("ios", "autoplay")
+        } else {
+            PlaybackAccessTokenParams::new("web", "site")
+        };
 
         Self {
-            query: gql.trim().to_string(),
+            query: QUERY,
+            variables: StreamPlaybackVariables {
+                login: username.to_string(),
+                params,
+            },
         }
     }
 }
 
-// Most fields here are optional because this struct is used in different queries,
-// not having them optional would cause issues when deserializing the response.
+impl GraphQLQuery<VideoPlaybackVariables> {
+    /// Used to retrieve playback access token for a VOD.
+    pub fn vod_playback_query(video_id: &str) -> Self {
+        const QUERY: &str = r"
+            query VideoPlayback($id: ID!, $params: PlaybackAccessTokenParams!) {
+                videoPlaybackAccessToken(id: $id, params: $params) {
+                    value
+                    signature
+                }
+            }
+        ";
+
+        Self {
+            query: QUERY,
+            variables: VideoPlaybackVariables {
+                id: video_id.to_string(),
+                params: PlaybackAccessTokenParams::new("web", "site"),
+            },
+        }
+    }
+}
+
+impl GraphQLQuery<ClipVariables> {
+    /// Used to retrieve a clip, its qualities and the token to sign their URLs.
+    pub fn clip_query(slug: &str) -> Self {
+        const QUERY: &str = r"
+            query Clip($slug: ID!, $params: PlaybackAccessTokenParams!) {
+                clip(slug: $slug) {
+                    title
+                    durationSeconds
+                    broadcaster {
+                        login
+                    }
+                    videoQualities {
+                        quality
+                        frameRate
+                        sourceURL
+                    }
+                    playbackAccessToken(params: $params) {
+                        value
+                        signature
+                    }
+                }
+            }
+        ";
+
+        Self {
+            query: QUERY,
+            variables: ClipVariables {
+                slug: slug.to_string(),
+                params: PlaybackAccessTokenParams::new("web", "site"),
+            },
+        }
+    }
+}
 
+// Variables of each query, field names match the `$variables` in the query text.
+
+#[derive(Serialize)]
+pub struct LoginVariables {
+    login: String,
+}
+
+impl LoginVariables {
+    fn new(login: &str) -> Self {
+        Self {
+            login: login.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ChannelProfileVariables {
+    login: String,
+    videos: u32,
+    clips: u32,
+}
+
+#[derive(Serialize)]
+pub struct UserPageVariables {
+    login: String,
+    first: u32,
+    after: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SearchVariables {
+    query: String,
+    first: u32,
+}
+
+#[derive(Serialize)]
+pub struct PageVariables {
+    first: u32,
+    after: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CategoryStreamsVariables {
+    name: String,
+    first: u32,
+    after: Option<String>,
+    options: StreamOptions,
+}
+
+#[derive(Serialize)]
+pub struct StreamsVariables {
+    first: u32,
+    after: Option<String>,
+    options: StreamOptions,
+}
+
+#[derive(Serialize)]
+pub struct StreamOptions {
+    sort: &'static str,
+    /// Language enum values like `EN`, every language if `None`.
+    languages: Option<Vec<String>>,
+}
+
+impl StreamOptions {
+    fn new(languages: &[String]) -> Self {
+        let languages: Vec<String> = languages
+            .iter()
+            .filter(|language| !language.is_empty())
+            .map(|language| language.to_uppercase())
+            .collect();
+
+        Self {
+            sort: "VIEWER_COUNT",
+            languages: (!languages.is_empty()).then_some(languages),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StreamPlaybackVariables {
+    login: String,
+    params: PlaybackAccessTokenParams,
+}
+
+#[derive(Serialize)]
+pub struct VideoPlaybackVariables {
+    id: String,
+    params: PlaybackAccessTokenParams,
+}
+
+#[derive(Serialize)]
+pub struct ClipVariables {
+    slug: String,
+    params: PlaybackAccessTokenParams,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackAccessTokenParams {
+    platform: &'static str,
+    player_backend: &'static str,
+    player_type: &'static str,
+}
+
+impl PlaybackAccessTokenParams {
+    fn new(platform: &'static str, player_type: &'static str) -> Self {
+        Self {
+            platform,
+            player_backend: "mediaplayer",
+            player_type,
+        }
+    }
+}
+
+/// Error returned by every GraphQL request.
+#[derive(Debug)]
+pub enum GraphQLError {
+    /// The request couldn't be sent, or its response couldn't be read.
+    Request(reqwest::Error),
+    /// Twitch rejected the request before running the query.
+    Status(StatusCode, String),
+    /// The query ran, but Twitch returned errors for it.
+    Query(Vec<GraphQLResponseError>),
+    /// The response had neither `data` nor `errors`.
+    MissingData,
+}
+
+impl fmt::Display for GraphQLError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Request(err) => write!(f, "GraphQL request failed: {err}"),
+            Self::Status(status, body) => write!(f, "GraphQL request failed: {status} - {body}"),
+            Self::Query(errors) => {
+                let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+                write!(f, "GraphQL query failed: {}", messages.join("; "))
+            }
+            Self::MissingData => write!(f, "GraphQL response has no data"),
+        }
+    }
+}
+
+impl std::error::Error for GraphQLError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Request(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GraphQLResponseError {
+    pub message: String,
+}
+
+/// Response to a single query, `Data` is the response struct of the operation.
+#[derive(Deserialize)]
+pub struct GraphQLResponse<Data> {
+    data: Option<Data>,
+    errors: Option<Vec<GraphQLResponseError>>,
+}
+
+impl<Data> GraphQLResponse<Data> {
+    /// Any error fails the whole query, even if some data was returned along with it.
+    pub fn into_data(self) -> Result<Data, GraphQLError> {
+        if let Some(errors) = self.errors.filter(|errors| !errors.is_empty()) {
+            return Err(GraphQLError::Query(errors));
+        }
+
+        self.data.ok_or(GraphQLError::MissingData)
+    }
+
+    /// Returns the data along with the errors of the fields that failed, which are null in the data.
+    ///
+    /// Only fails if there is no data at all.
+    pub fn into_partial_data(self) -> Result<(Data, Vec<GraphQLResponseError>), GraphQLError> {
+        let errors = self.errors.unwrap_or_default();
+
+        match self.data {
+            Some(data) => Ok((data, errors)),
+            None if errors.is_empty() => Err(GraphQLError::MissingData),
+            None => Err(GraphQLError::Query(errors)),
+        }
+    }
+}
+
+// Response of each operation, each one only has the fields its query asks for,
+// fields are only optional where Twitch's schema allows them to be null.
+
+/// Response of `full_user`.
 #[derive(Deserialize)]
-pub struct GraphQLResponse {
-    pub data: GraphQLResponseData,
+pub struct FullUserData {
+    pub user: Option<GraphQLResponseFullUser>,
 }
 
+/// Response of `live_now`.
 #[derive(Deserialize)]
-pub struct GraphQLResponseData {
-    pub user: Option<GraphQLResponseUser>,
-    #[serde(
-        rename = "streamPlaybackAccessToken",
-        skip_serializing_if = "Option::is_none"
-    )]
+pub struct LiveNowData {
+    pub user: Option<GraphQLResponseLiveNowUser>,
+}
+
+/// Response of `channel_profile`.
+#[derive(Deserialize)]
+pub struct ChannelProfileData {
+    pub user: Option<GraphQLResponseProfileUser>,
+}
+
+/// Response of `follows`.
+#[derive(Deserialize)]
+pub struct FollowsData {
+    pub user: Option<GraphQLResponseFollowsUser>,
+}
+
+/// Response of `archive_videos`.
+#[derive(Deserialize)]
+pub struct ArchiveVideosData {
+    pub user: Option<GraphQLResponseArchiveUser>,
+}
+
+/// Response of `search_channels`.
+#[derive(Deserialize)]
+pub struct SearchChannelsData {
+    #[serde(rename = "searchUsers")]
+    pub search_users: Option<GraphQLResponseUserConnection>,
+}
+
+/// Response of `top_categories`.
+#[derive(Deserialize)]
+pub struct TopCategoriesData {
+    pub games: Option<GraphQLResponseCategoryConnection>,
+}
+
+/// Response of `category_streams`.
+#[derive(Deserialize)]
+pub struct CategoryStreamsData {
+    pub game: Option<GraphQLResponseCategoryStreams>,
+}
+
+/// Response of `top_streams`.
+#[derive(Deserialize)]
+pub struct TopStreamsData {
+    pub streams: Option<GraphQLResponseStreamConnection>,
+}
+
+#[derive(Deserialize)]
+pub struct StreamPlaybackData {
+    #[serde(rename = "streamPlaybackAccessToken")]
     pub stream_playback_access_token: Option<StreamPlaybackAccessToken>,
 }
 
+#[derive(Deserialize)]
+pub struct VideoPlaybackData {
+    #[serde(rename = "videoPlaybackAccessToken")]
+    pub video_playback_access_token: Option<StreamPlaybackAccessToken>,
+}
+
+#[derive(Deserialize)]
+pub struct ClipData {
+    pub clip: Option<GraphQLResponseClip>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseUserConnection {
+    pub edges: Vec<GraphQLResponseUserEdge>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseUserEdge {
+    /// Null for users that were banned or deleted.
+    pub node: Option<GraphQLResponseUser>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseFollowers {
+    #[serde(rename = "totalCount")]
+    pub total_count: i64,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponsePageInfo {
+    #[serde(rename = "hasNextPage")]
+    pub has_next_page: bool,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseCategoryConnection {
+    pub edges: Vec<GraphQLResponseCategoryEdge>,
+    #[serde(rename = "pageInfo")]
+    pub page_info: GraphQLResponsePageInfo,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseCategoryEdge {
+    pub cursor: Option<String>,
+    pub node: GraphQLResponseCategory,
+}
+
+/// Category listed by `top_categories`.
+#[derive(Deserialize)]
+pub struct GraphQLResponseCategory {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+    #[serde(rename = "viewersCount")]
+    pub viewers_count: Option<i64>,
+    #[serde(rename = "boxArtURL")]
+    pub box_art_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseCategoryStreams {
+    pub streams: Option<GraphQLResponseStreamConnection>,
+}
+
+/// Streams of `category_streams` and `top_streams`, both ask for the same fields.
+#[derive(Deserialize)]
+pub struct GraphQLResponseStreamConnection {
+    pub edges: Vec<GraphQLResponseStreamEdge>,
+    #[serde(rename = "pageInfo")]
+    pub page_info: GraphQLResponsePageInfo,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseStreamEdge {
+    pub cursor: Option<String>,
+    pub node: GraphQLResponseDirectoryStream,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseDirectoryStream {
+    #[serde(flatten)]
+    pub stream: GraphQLResponseStream,
+    pub broadcaster: Option<GraphQLResponseBroadcaster>,
+}
+
+/// User found by `search_channels`.
 #[derive(Deserialize)]
 pub struct GraphQLResponseUser {
-    pub id: Option<String>,
+    pub login: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+    #[serde(rename = "profileImageURL")]
+    pub profile_image_url: Option<String>,
+    pub followers: Option<GraphQLResponseFollowers>,
+    pub stream: Option<GraphQLResponseSearchStream>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseSearchStream {
+    #[serde(rename = "viewersCount")]
+    pub viewers_count: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseFullUser {
+    pub id: String,
     #[serde(rename = "profileImageURL")]
     pub profile_image_url: Option<String>,
     #[serde(rename = "subscriptionProducts")]
@@ -101,102 +818,238 @@ pub struct GraphQLResponseUser {
 }
 
 #[derive(Deserialize)]
-pub struct SubscriptionProduct {
-    pub emotes: Vec<GraphQLResponseEmote>,
+pub struct GraphQLResponseLiveNowUser {
+    pub login: String,
+    /// Null if the user is offline.
+    pub stream: Option<GraphQLResponseStream>,
 }
 
 #[derive(Deserialize)]
-pub struct StreamPlaybackAccessToken {
-    pub value: String,
-    pub signature: String,
+pub struct GraphQLResponseProfileUser {
+    pub login: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+    pub description: Option<String>,
+    #[serde(rename = "profileImageURL")]
+    pub profile_image_url: Option<String>,
+    #[serde(rename = "bannerImageURL")]
+    pub banner_image_url: Option<String>,
+    pub followers: Option<GraphQLResponseFollowers>,
+    pub channel: Option<GraphQLResponseChannel>,
+    pub panels: Option<Vec<GraphQLResponsePanel>>,
+    pub videos: Option<GraphQLResponseVideoConnection>,
+    pub clips: Option<GraphQLResponseClipConnection>,
 }
 
 #[derive(Deserialize)]
-pub struct GraphQLResponseEmote {
+pub struct GraphQLResponseFollowsUser {
+    pub follows: Option<GraphQLResponseFollowConnection>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseFollowConnection {
+    pub edges: Vec<GraphQLResponseFollowEdge>,
+    #[serde(rename = "pageInfo")]
+    pub page_info: GraphQLResponsePageInfo,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseFollowEdge {
+    pub cursor: String,
+    /// Null for users that were banned or deleted.
+    pub node: Option<GraphQLResponseFollowedUser>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseFollowedUser {
+    pub login: String,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseArchiveUser {
+    pub videos: Option<GraphQLResponseArchiveVideoConnection>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseArchiveVideoConnection {
+    pub edges: Vec<GraphQLResponseArchiveVideoEdge>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseArchiveVideoEdge {
+    pub node: GraphQLResponseArchiveVideo,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseArchiveVideo {
     pub id: String,
-    pub token: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
 }
 
-// Persistent queries and their responses.
+#[derive(Deserialize)]
+pub struct GraphQLResponseChannel {
+    #[serde(rename = "socialMedias")]
+    pub social_medias: Option<Vec<GraphQLResponseSocialMedia>>,
+    pub schedule: Option<GraphQLResponseSchedule>,
+}
 
-// I don't plan on querying the stream info when refreshing users, so this query is really good for this.
+#[derive(Deserialize)]
+pub struct GraphQLResponseSocialMedia {
+    pub name: String,
+    pub title: String,
+    pub url: String,
+}
 
-#[derive(Serialize)]
-pub struct UseLiveQuery {
-    #[serde(rename = "operationName")]
-    operation_name: String,
-    variables: ChannelLoginVariable,
-    extensions: QueryExtensions,
+#[derive(Deserialize)]
+pub struct GraphQLResponseSchedule {
+    pub segments: Option<Vec<GraphQLResponseScheduleSegment>>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct ChannelLoginVariable {
-    #[serde(rename = "channelLogin")]
-    channel_login: String,
+#[derive(Deserialize)]
+pub struct GraphQLResponseScheduleSegment {
+    pub id: String,
+    #[serde(rename = "startAt")]
+    pub start_at: String,
+    #[serde(rename = "endAt")]
+    pub end_at: Option<String>,
+    pub title: Option<String>,
+    #[serde(rename = "isCancelled")]
+    pub is_cancelled: Option<bool>,
+    pub categories: Option<Vec<GraphQLResponseScheduleCategory>>,
 }
 
-impl ChannelLoginVariable {
-    pub fn new(channel_login: &str) -> Self {
-        Self {
-            channel_login: channel_login.to_string(),
-        }
-    }
+#[derive(Deserialize)]
+pub struct GraphQLResponseScheduleCategory {
+    pub name: String,
 }
 
-impl UseLiveQuery {
-    pub fn new(channel_login: &str) -> Self {
-        Self {
-            operation_name: String::from("UseLive"),
-            variables: ChannelLoginVariable::new(channel_login),
-            extensions: QueryExtensions::new(USE_LIVE_QUERY_HASH),
-        }
-    }
+/// Only default panels have content, extension panels just have their `id` and `type`.
+#[derive(Deserialize)]
+pub struct GraphQLResponsePanel {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[serde(rename = "imageURL")]
+    pub image_url: Option<String>,
+    #[serde(rename = "linkURL")]
+    pub link_url: Option<String>,
 }
 
 #[derive(Deserialize)]
-pub struct UseLiveResponse {
-    pub data: UseLiveResponseData,
+pub struct GraphQLResponseClipConnection {
+    pub edges: Vec<GraphQLResponseClipEdge>,
 }
 
 #[derive(Deserialize)]
-pub struct UseLiveResponseData {
-    pub user: UseLiveResponseUser,
+pub struct GraphQLResponseClipEdge {
+    pub node: GraphQLResponseClipPreview,
 }
 
 #[derive(Deserialize)]
-pub struct UseLiveResponseUser {
-    pub login: String,
-    pub stream: Option<UseLiveResponseStream>,
+pub struct GraphQLResponseClipPreview {
+    pub slug: String,
+    pub title: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "durationSeconds")]
+    pub duration_seconds: u64,
+    #[serde(rename = "viewCount")]
+    pub view_count: Option<i64>,
+    #[serde(rename = "thumbnailURL")]
+    pub thumbnail_url: Option<String>,
 }
 
 #[derive(Deserialize)]
-pub struct UseLiveResponseStream {
+pub struct GraphQLResponseVideoConnection {
+    pub edges: Vec<GraphQLResponseVideoEdge>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseVideoEdge {
+    pub node: GraphQLResponseVideo,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseVideo {
+    pub id: String,
     #[serde(rename = "createdAt")]
     pub created_at: String,
+    pub title: Option<String>,
+    #[serde(rename = "lengthSeconds")]
+    pub length_seconds: Option<u64>,
+    #[serde(rename = "viewCount")]
+    pub view_count: Option<i64>,
+    #[serde(rename = "previewThumbnailURL")]
+    pub preview_thumbnail_url: Option<String>,
 }
 
-// Every persistent query has these fields
+/// Stream fields shared by `live_now`, `category_streams` and `top_streams`.
+#[derive(Deserialize)]
+pub struct GraphQLResponseStream {
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<String>,
+    pub title: Option<String>,
+    #[serde(rename = "viewersCount")]
+    pub viewers_count: Option<i64>,
+    #[serde(rename = "previewImageURL")]
+    pub preview_image_url: Option<String>,
+    pub game: Option<GraphQLResponseStreamGame>,
+    #[serde(rename = "freeformTags")]
+    pub freeform_tags: Option<Vec<GraphQLResponseTag>>,
+}
 
-#[derive(Serialize, Deserialize)]
-pub struct QueryExtensions {
-    #[serde(rename = "persistedQuery")]
-    persisted_query: PersistedQuery,
+#[derive(Deserialize)]
+pub struct GraphQLResponseStreamGame {
+    pub name: String,
 }
 
-impl QueryExtensions {
-    pub fn new(hash: &str) -> Self {
-        Self {
-            persisted_query: PersistedQuery {
-                version: 1,
-                sha256_hash: hash.to_string(),
-            },
-        }
-    }
+#[derive(Deserialize)]
+pub struct GraphQLResponseTag {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct SubscriptionProduct {
+    pub emotes: Vec<GraphQLResponseEmote>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct PersistedQuery {
-    version: u64,
-    #[serde(rename = "sha256Hash")]
-    sha256_hash: String,
+#[derive(Deserialize)]
+pub struct StreamPlaybackAccessToken {
+    pub value: String,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseClip {
+    pub title: String,
+    #[serde(rename = "durationSeconds")]
+    pub duration_seconds: u64,
+    pub broadcaster: Option<GraphQLResponseBroadcaster>,
+    #[serde(rename = "videoQualities")]
+    pub video_qualities: Vec<ClipVideoQuality>,
+    #[serde(rename = "playbackAccessToken")]
+    pub playback_access_token: StreamPlaybackAccessToken,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseBroadcaster {
+    pub login: String,
+}
+
+#[derive(Deserialize)]
+pub struct ClipVideoQuality {
+    pub quality: String,
+    #[serde(rename = "frameRate")]
+    pub frame_rate: f64,
+    #[serde(rename = "sourceURL")]
+    pub source_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQLResponseEmote {
+    pub id: String,
+    pub token: String,
 }
@@ -0,0 +1,594 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use serde::Serialize;
+use sqlx::{Pool, Row, Sqlite};
+use tauri::{
+    async_runtime::{self, Mutex},
+    AppHandle, Emitter, Manager,
+};
+use tokio::{fs::File, io::AsyncWriteExt, process::Command};
+
+use crate::{util, AppState};
+
+use super::{
+    m3u8::{Segment, Variant},
+    proxy, quality,
+};
+
+/// After this many failed playlist requests in a row the stream is considered offline.
+const MAX_FAILED_REFRESHES: u32 = 5;
+
+lazy_static! {
+    static ref RECORDINGS: Mutex<HashMap<String, Recording>> = Mutex::new(HashMap::new());
+}
+
+struct Recording {
+    status: RecordingStatus,
+    /// Also identifies the recording, a newer one of the same user has a different flag.
+    cancel: Arc<AtomicBool>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordingState {
+    Recording,
+    Remuxing,
+    Finished,
+    Failed,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingStatus {
+    pub username: String,
+    pub path: String,
+    pub quality: String,
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    pub segments: u64,
+    pub bytes: u64,
+    /// Segments that rolled out of the playlist before they could be downloaded.
+    pub missed_segments: u64,
+    pub state: RecordingState,
+    pub error: Option<String>,
+}
+
+/// Starts recording a stream in the background.
+///
+/// `quality` is the Twitch quality name, like `chunked` (source) or `720p60`, if not found, the closest lower quality is used,
+/// if not provided, the best quality.
+/// Recordings are saved to `directory` or the `rt` folder in the user's videos directory,
+/// if `remux` is set, the recording is remuxed to `.mp4` with ffmpeg when it ends.
+#[tauri::command]
+pub async fn start_recording(
+    app_handle: AppHandle,
+    username: String,
+    quality: Option<String>,
+    directory: Option<String>,
+    remux: bool,
+) -> Result<RecordingStatus, String> {
+    if username.is_empty() {
+        return Err(String::from("No username provided"));
+    }
+
+    let directory = match directory {
+        Some(directory) => PathBuf::from(directory),
+        None => match app_handle.path().video_dir() {
+            Ok(dir) => dir.join("rt"),
+            Err(err) => return Err(format!("Failed to get videos directory: {err}")),
+        },
+    };
+
+    start(app_handle, username, quality, directory, remux, None)
+        .await
+        .map_err(|err| format!("Failed to start recording: {err}"))
+}
+
+#[tauri::command]
+pub async fn stop_recording(username: &str) -> Result<(), String> {
+    let recordings = RECORDINGS.lock().await;
+
+    let Some(recording) = recordings.get(username) else {
+        return Err(format!("No recording found for '{username}'"));
+    };
+
+    recording.cancel.store(true, Ordering::SeqCst);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_recordings() -> Result<Vec<RecordingStatus>, String> {
+    let recordings = RECORDINGS.lock().await;
+
+    Ok(recordings
+        .values()
+        .map(|recording| recording.status.clone())
+        .collect())
+}
+
+/// Returns if the file at `path` is being written by a running recording.
+pub async fn is_recording_path(path: &Path) -> bool {
+    RECORDINGS.lock().await.values().any(|recording| {
+        recording.status.state != RecordingState::Finished
+            && recording.status.state != RecordingState::Failed
+            && Path::new(&recording.status.path) == path
+    })
+}
+
+/// Returns every file written by the recorder, only these are ever removed by the retention rules.
+pub async fn recorded_files(db: &Pool<Sqlite>) -> Result<Vec<PathBuf>> {
+    let query = "SELECT path FROM recordings";
+
+    let rows = sqlx::query(query).fetch_all(db).await?;
+
+    let mut paths = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let path: String = row.try_get("path")?;
+        paths.push(PathBuf::from(path));
+    }
+
+    Ok(paths)
+}
+
+/// Removes a recording from the ones written by the recorder, after it was deleted.
+pub async fn forget_recording(db: &Pool<Sqlite>, path: &Path) -> Result<()> {
+    let query = "DELETE FROM recordings WHERE path = ?";
+
+    sqlx::query(query)
+        .bind(path.to_string_lossy())
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Starts recording in the background, the recording stops by itself once it reaches `max_bytes`.
+pub async fn start(
+    app_handle: AppHandle,
+    username: String,
+    quality: Option<String>,
+    directory: PathBuf,
+    remux: bool,
+    max_bytes: Option<u64>,
+) -> Result<RecordingStatus> {
+    let started_at = util::unix_timestamp();
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    // Reserved before anything is awaited, so two starts can't record the same stream
+    {
+        let mut recordings = RECORDINGS.lock().await;
+
+        // A recording being remuxed still owns its file and status
+        if recordings.get(&username).is_some_and(|recording| {
+            matches!(
+                recording.status.state,
+                RecordingState::Recording | RecordingState::Remuxing
+            )
+        }) {
+            return Err(anyhow!("'{username}' is already being recorded"));
+        }
+
+        recordings.insert(
+            username.clone(),
+            Recording {
+                status: RecordingStatus {
+                    username: username.clone(),
+                    path: String::new(),
+                    quality: String::new(),
+                    started_at,
+                    ended_at: None,
+                    segments: 0,
+                    bytes: 0,
+                    missed_segments: 0,
+                    state: RecordingState::Recording,
+                    error: None,
+                },
+                cancel: Arc::clone(&cancel),
+            },
+        );
+    }
+
+    let users_db = {
+        let state = app_handle.state::<Mutex<AppState>>();
+        let users_db = state.lock().await.users_db.clone().unwrap();
+        users_db
+    };
+
+    let (variant, path, file) = match create_recording(
+        &users_db,
+        &username,
+        quality.as_deref(),
+        &directory,
+        started_at,
+    )
+    .await
+    {
+        Ok(recording) => recording,
+        Err(err) => {
+            RECORDINGS.lock().await.remove(&username);
+            return Err(err);
+        }
+    };
+
+    let status = {
+        let mut recordings = RECORDINGS.lock().await;
+
+        let Some(recording) = recordings
+            .get_mut(&username)
+            .filter(|recording| Arc::ptr_eq(&recording.cancel, &cancel))
+        else {
+            return Err(anyhow!("Recording of '{username}' was removed"));
+        };
+
+        recording.status.path = path.to_string_lossy().to_string();
+        recording.status.quality = variant.video().unwrap_or_default().to_string();
+        recording.status.clone()
+    };
+
+    info!("Recording '{username}' to '{}'", path.display());
+    emit_updated_recordings(&app_handle);
+
+    async_runtime::spawn(async move {
+        let result = record(&app_handle, &username, &variant, file, &cancel, max_bytes).await;
+
+        if let Err(err) = &result {
+            error!("Recording of '{username}' failed: {err}");
+        }
+
+        let remux_result = if remux && result.is_ok() {
+            update_status(&app_handle, &username, &cancel, |status| {
+                status.state = RecordingState::Remuxing;
+            })
+            .await;
+
+            remux_to_mp4(&path).await.map(Some)
+        } else {
+            Ok(None)
+        };
+
+        if let Ok(Some(mp4_path)) = &remux_result {
+            let query = "UPDATE recordings SET path = ? WHERE path = ?";
+
+            if let Err(err) = sqlx::query(query)
+                .bind(mp4_path.to_string_lossy())
+                .bind(path.to_string_lossy())
+                .execute(&users_db)
+                .await
+            {
+                error!(
+                    "Failed to save remuxed recording '{}': {err}",
+                    mp4_path.display()
+                );
+            }
+        }
+
+        update_status(&app_handle, &username, &cancel, |status| {
+            status.ended_at = Some(util::unix_timestamp());
+
+            if let Ok(Some(mp4_path)) = &remux_result {
+                status.path = mp4_path.to_string_lossy().to_string();
+            }
+
+            match result.and(remux_result) {
+                Ok(_) => status.state = RecordingState::Finished,
+                Err(err) => {
+                    status.state = RecordingState::Failed;
+                    status.error = Some(err.to_string());
+                }
+            }
+        })
+        .await;
+    });
+
+    Ok(status)
+}
+
+/// Picks the quality and creates the file of a new recording, the file is added to the ones written by the recorder.
+async fn create_recording(
+    users_db: &Pool<Sqlite>,
+    username: &str,
+    quality: Option<&str>,
+    directory: &Path,
+    started_at: i64,
+) -> Result<(Variant, PathBuf, File)> {
+    let master_playlist = proxy::fetch_master_playlist(username, false).await?;
+
+    let variant = quality::select_variant(&master_playlist, quality)
+        .cloned()
+        .ok_or_else(|| anyhow!("Master playlist has no variants"))?;
+
+    tokio::fs::create_dir_all(directory).await?;
+
+    let file_name = format!("{}-{started_at}.ts", sanitize_file_name(username));
+    let path = directory.join(file_name);
+    let file = File::create(&path).await?;
+
+    let query = "INSERT INTO recordings (path, username, started_at) VALUES (?, ?, ?) ON CONFLICT (path) DO NOTHING";
+
+    sqlx::query(query)
+        .bind(path.to_string_lossy())
+        .bind(username)
+        .bind(started_at)
+        .execute(users_db)
+        .await?;
+
+    Ok((variant, path, file))
+}
+
+/// Usernames come from the frontend, only the characters allowed in Twitch logins are kept in file names.
+fn sanitize_file_name(username: &str) -> String {
+    username
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Follows the media playlist, appending every new segment to the file in order.
+///
+/// Segments are tracked by their media sequence number, so nothing is written twice.
+/// Ad breaks are detected the same way as in the proxy and replaced with the segments of the backup stream
+/// covering the same time, both streams share the same clock so their program date times can be compared.
+async fn record(
+    app_handle: &AppHandle,
+    username: &str,
+    variant: &Variant,
+    mut file: File,
+    cancel: &Arc<AtomicBool>,
+    max_bytes: Option<u64>,
+) -> Result<()> {
+    let mut next_sequence: Option<u64> = None;
+    let mut last_date: Option<String> = None;
+    let mut backup_url: Option<String> = None;
+    let mut failed_refreshes = 0;
+    let mut written_bytes: u64 = 0;
+
+    while !cancel.load(Ordering::Relaxed) {
+        let playlist = match proxy::fetch_media_playlist(&variant.uri).await {
+            Ok(playlist) => {
+                failed_refreshes = 0;
+                playlist
+            }
+            Err(err) => {
+                failed_refreshes += 1;
+
+                if failed_refreshes >= MAX_FAILED_REFRESHES {
+                    info!("Stream of '{username}' appears to be offline, stopping recording");
+                    break;
+                }
+
+                warn!("Failed to refresh playlist of '{username}': {err}");
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                continue;
+            }
+        };
+
+        let first_sequence = playlist.media_sequence();
+        let ads = proxy::find_ad_segments(&playlist);
+
+        let mut missed = next_sequence.map_or(0, |next| first_sequence.saturating_sub(next));
+        if missed > 0 {
+            warn!("Missed {missed} segments of '{username}'");
+        }
+
+        let new_segments: Vec<(&Segment, bool)> = playlist
+            .segments
+            .iter()
+            .zip(ads)
+            .enumerate()
+            .filter(|(i, _)| next_sequence.is_none_or(|next| first_sequence + *i as u64 >= next))
+            .map(|(_, segment)| segment)
+            .collect();
+
+        let end_sequence = first_sequence + playlist.segments.len() as u64;
+        next_sequence = Some(next_sequence.map_or(end_sequence, |next| next.max(end_sequence)));
+
+        let mut segments = Vec::with_capacity(new_segments.len());
+        let mut backup_segments: Option<Vec<Segment>> = None;
+
+        for (i, (segment, is_ad)) in new_segments.iter().enumerate() {
+            if !is_ad {
+                segments.push((*segment).clone());
+                continue;
+            }
+
+            // The whole ad break is replaced at once, from its first segment
+            if i > 0 && new_segments[i - 1].1 {
+                continue;
+            }
+
+            if backup_segments.is_none() {
+                backup_segments = Some(
+                    match fetch_backup_segments(username, variant, &mut backup_url).await {
+                        Ok(backup_segments) => backup_segments,
+                        Err(err) => {
+                            warn!("Failed to fetch backup stream of '{username}', skipping ads: {err}");
+                            Vec::new()
+                        }
+                    },
+                );
+            }
+
+            let start = segment.program_date_time();
+            let end = new_segments[i..]
+                .iter()
+                .find(|(_, is_ad)| !is_ad)
+                .and_then(|(segment, _)| segment.program_date_time());
+
+            segments.extend(
+                backup_segments
+                    .iter()
+                    .flatten()
+                    .filter(|backup_segment| {
+                        backup_segment.program_date_time().is_some_and(|date| {
+                            start.is_none_or(|start| date >= start)
+                                && end.is_none_or(|end| date < end)
+                        })
+                    })
+                    .cloned(),
+            );
+        }
+
+        // Back on the main stream, the next ad break gets a new backup URL in case it expired
+        if backup_segments.is_none() {
+            backup_url = None;
+        }
+
+        for segment in segments {
+            // Already written from the other stream
+            if segment
+                .program_date_time()
+                .zip(last_date.as_deref())
+                .is_some_and(|(date, last_date)| date <= last_date)
+            {
+                continue;
+            }
+
+            if let Some(date) = segment.program_date_time() {
+                last_date = Some(date.to_string());
+            }
+
+            let bytes = match proxy::fetch_segment(&segment.uri).await {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    warn!("Failed to download segment of '{username}', skipping it: {err}");
+                    missed += 1;
+                    continue;
+                }
+            };
+
+            if max_bytes.is_some_and(|max| written_bytes + bytes.len() as u64 > max) {
+                info!("Recording of '{username}' reached the disk usage limit, stopping");
+                cancel.store(true, Ordering::SeqCst);
+                break;
+            }
+
+            file.write_all(&bytes).await?;
+            written_bytes += bytes.len() as u64;
+
+            update_status(app_handle, username, cancel, |status| {
+                status.segments += 1;
+                status.bytes += bytes.len() as u64;
+            })
+            .await;
+
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+        }
+
+        if missed > 0 {
+            update_status(app_handle, username, cancel, |status| {
+                status.missed_segments += missed;
+            })
+            .await;
+        }
+
+        if playlist.end_list {
+            break;
+        }
+
+        // Refresh around every half segment, as recommended for live playlists
+        let segment_duration = playlist.segments.last().map_or(2.0, Segment::duration);
+
+        tokio::time::sleep(Duration::from_secs_f64(segment_duration / 2.0)).await;
+    }
+
+    file.flush().await?;
+
+    Ok(())
+}
+
+/// Returns the live segments of the backup stream, its media playlist URL is only fetched once per ad break.
+async fn fetch_backup_segments(
+    username: &str,
+    variant: &Variant,
+    backup_url: &mut Option<String>,
+) -> Result<Vec<Segment>> {
+    let url = match backup_url {
+        Some(url) => url.clone(),
+        None => backup_url
+            .insert(proxy::fetch_backup_stream_url(username, Some(variant)).await?)
+            .clone(),
+    };
+
+    let playlist = proxy::fetch_media_playlist(&url).await?;
+    let ads = proxy::find_ad_segments(&playlist);
+
+    Ok(playlist
+        .segments
+        .into_iter()
+        .zip(ads)
+        .filter(|(_, is_ad)| !is_ad)
+        .map(|(segment, _)| segment)
+        .collect())
+}
+
+/// Copies the streams to a `.mp4` container, the `.ts` file is removed if successful.
+async fn remux_to_mp4(path: &Path) -> Result<PathBuf> {
+    let mp4_path = path.with_extension("mp4");
+
+    let output = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-i")
+        .arg(path)
+        .args(["-c", "copy"])
+        .arg(&mp4_path)
+        .output()
+        .await
+        .map_err(|err| anyhow!("Failed to run ffmpeg: {err}"))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    tokio::fs::remove_file(path).await?;
+
+    Ok(mp4_path)
+}
+
+/// Updates the status of the recording of `username` that owns `cancel`, a newer recording of the same user is left alone.
+async fn update_status(
+    app_handle: &AppHandle,
+    username: &str,
+    cancel: &Arc<AtomicBool>,
+    update: impl FnOnce(&mut RecordingStatus),
+) {
+    if let Some(recording) = RECORDINGS
+        .lock()
+        .await
+        .get_mut(username)
+        .filter(|recording| Arc::ptr_eq(&recording.cancel, cancel))
+    {
+        update(&mut recording.status);
+    }
+
+    emit_updated_recordings(app_handle);
+}
+
+fn emit_updated_recordings(app_handle: &AppHandle) {
+    if let Err(err) = app_handle.emit("updated_recordings", ()) {
+        error!("Failed to emit 'updated_recordings' event: {err}");
+    }
+}
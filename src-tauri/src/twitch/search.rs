@@ -0,0 +1,100 @@
+use serde::Serialize;
+use sqlx::Row;
+use tauri::{async_runtime::Mutex, State};
+
+use crate::AppState;
+
+use super::{
+    main,
+    query::{GraphQLQuery, SearchChannelsData},
+};
+
+/// How many channels are returned per search.
+const SEARCH_RESULTS: u32 = 20;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchChannel {
+    /// Login used to add the user.
+    pub username: String,
+    pub display_name: String,
+    /// URL of the profile picture.
+    pub avatar: String,
+    pub followers: i64,
+    /// Viewers of the current stream, `None` if the channel is offline.
+    pub viewers: Option<i64>,
+    /// If the user is already in the Twitch users.
+    pub added: bool,
+}
+
+/// Returns the channels matching a query, so they can be added without knowing their exact login.
+#[tauri::command]
+pub async fn search_channels(
+    state: State<'_, Mutex<AppState>>,
+    query: String,
+) -> Result<Vec<SearchChannel>, String> {
+    let query = query.trim();
+
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let gql = GraphQLQuery::search_channels(query, SEARCH_RESULTS);
+
+    let response: SearchChannelsData = match main::send_query(gql).await {
+        Ok(response) => response,
+        Err(err) => {
+            return Err(format!("Failed to search channels: {err}"));
+        }
+    };
+
+    let added: Vec<String> = {
+        let state = state.lock().await;
+        let users_db = state.users_db.as_ref().unwrap();
+
+        let rows = match sqlx::query("SELECT username FROM twitch")
+            .fetch_all(users_db)
+            .await
+        {
+            Ok(rows) => rows,
+            Err(err) => {
+                return Err(format!("Failed to fetch usernames from database: {err}"));
+            }
+        };
+
+        let mut usernames = Vec::with_capacity(rows.len());
+
+        for row in rows {
+            usernames.push(row.try_get("username").map_err(|e| e.to_string())?);
+        }
+
+        usernames
+    };
+
+    let channels = response
+        .search_users
+        .map(|users| users.edges)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|edge| {
+            let user = edge.node?;
+            let username = user.login;
+
+            Some(SearchChannel {
+                display_name: user.display_name,
+                avatar: user.profile_image_url.unwrap_or_default(),
+                followers: user
+                    .followers
+                    .map(|followers| followers.total_count)
+                    .unwrap_or_default(),
+                viewers: user
+                    .stream
+                    .map(|stream| stream.viewers_count.unwrap_or_default()),
+                added: added.contains(&username),
+                username,
+            })
+        })
+        .collect();
+
+    Ok(channels)
+}
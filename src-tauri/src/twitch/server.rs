@@ -0,0 +1,498 @@
+use std::{collections::HashSet, convert::Infallible, net::Ipv4Addr, time::Duration};
+
+use anyhow::{anyhow, Result};
+use http_body_util::Full;
+use hyper::{
+    body::{Bytes, Incoming},
+    header::{HeaderValue, ACCESS_CONTROL_ALLOW_ORIGIN, CACHE_CONTROL, CONTENT_TYPE, ORIGIN, VARY},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use tauri::{
+    async_runtime::{self, Mutex},
+    AppHandle, Manager,
+};
+use tokio::net::TcpListener;
+
+use crate::AppState;
+
+use super::{
+    dvr,
+    m3u8::{MasterEntry, MasterPlaylist},
+    proxy, quality, vod,
+};
+
+/// Port tried first so the URLs stay the same between restarts, a random one is used if it's taken.
+const DEFAULT_PORT: u16 = 9247;
+
+/// Origins of the app's webview, `tauri://localhost` on Linux and macOS and `http://tauri.localhost` on Windows.
+const WEBVIEW_ORIGINS: [&str; 3] = [
+    "tauri://localhost",
+    "http://tauri.localhost",
+    "https://tauri.localhost",
+];
+
+/// `build.devUrl`, the webview loads the frontend from it in development.
+const DEV_ORIGIN: &str = "http://localhost:5173";
+
+/// How often the state left by external players is checked.
+const STALE_STATE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+const PLAYLIST_CONTENT_TYPE: &str = "application/vnd.apple.mpegurl";
+const SEGMENT_CONTENT_TYPE: &str = "video/mp2t";
+
+lazy_static! {
+    static ref SERVER_PORT: Mutex<Option<u16>> = Mutex::new(None);
+    /// Hosts of the variants and segments of the playlists served, only these can be proxied,
+    /// otherwise this would be an open proxy for anything running locally.
+    static ref ALLOWED_HOSTS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Starts the local HLS server, any HLS player can play a stream through it with the ads already filtered.
+///
+/// Routes:
+/// - `/twitch/<window>/<username>/master.m3u8`: master playlist, `?segments=true` also proxies the segments,
+///   `?dvr=true` keeps the last minutes of the stream in the playlists so it can be rewound,
+///   `?quality=<quality>` only serves that quality, instead of the preferred quality of the channel.
+/// - `/twitch/<window>/<username>/media.m3u8?url=<url>`: media playlist of a variant.
+/// - `/twitch/segment?url=<url>`: a single segment.
+/// - `/twitch/dvr/<buffer>/<sequence>.ts`: a segment from a DVR buffer.
+/// - `/twitch/vod/<video id>/master.m3u8`: master playlist of a VOD, `?segments=true` and `?quality=` work the same.
+/// - `/twitch/vod/media.m3u8?url=<url>`: media playlist of a VOD variant.
+///
+/// `<window>` identifies the player, it's the window label for players in the app, external players can use any name
+/// other than `vod`.
+pub async fn start(app_handle: AppHandle) -> Result<()> {
+    dvr::clear_cache().await;
+
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, DEFAULT_PORT)).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!("Failed to bind HLS server to port {DEFAULT_PORT}, using a random port: {err}");
+            TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?
+        }
+    };
+
+    let port = listener.local_addr()?.port();
+    *SERVER_PORT.lock().await = Some(port);
+
+    info!("HLS server listening on http://127.0.0.1:{port}");
+
+    let cleanup_handle = app_handle.clone();
+    async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(STALE_STATE_CHECK_INTERVAL).await;
+            proxy::remove_stale_states(&cleanup_handle).await;
+        }
+    });
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                error!("Failed to accept HLS server connection: {err}");
+                continue;
+            }
+        };
+
+        let app_handle = app_handle.clone();
+
+        async_runtime::spawn(async move {
+            let service = service_fn(move |request| handle_request(app_handle.clone(), request));
+
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                error!("Failed to serve HLS server connection: {err}");
+            }
+        });
+    }
+}
+
+/// Returns the local URL of the master playlist of a stream.
+///
+/// If `quality` is provided, e.g. `audio_only`, it is played instead of the preferred quality of the channel.
+#[tauri::command]
+pub async fn stream_proxy_url(
+    window_label: &str,
+    username: &str,
+    quality: Option<String>,
+) -> Result<String, String> {
+    if username.is_empty() {
+        return Err(String::from("No username provided"));
+    }
+
+    let Some(port) = *SERVER_PORT.lock().await else {
+        return Err(String::from("HLS server is not running"));
+    };
+
+    let mut url = format!(
+        "http://127.0.0.1:{port}/twitch/{}/{}/master.m3u8?dvr=true",
+        urlencoding::encode(window_label),
+        urlencoding::encode(username)
+    );
+
+    if let Some(quality) = quality {
+        url.push_str(&format!("&quality={}", urlencoding::encode(&quality)));
+    }
+
+    Ok(url)
+}
+
+/// Returns the local URL of the master playlist of a VOD.
+#[tauri::command]
+pub async fn vod_proxy_url(video_id: &str) -> Result<String, String> {
+    if video_id.is_empty() {
+        return Err(String::from("No video ID provided"));
+    }
+
+    if !video_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid video ID '{video_id}'"));
+    }
+
+    let Some(port) = *SERVER_PORT.lock().await else {
+        return Err(String::from("HLS server is not running"));
+    };
+
+    Ok(format!(
+        "http://127.0.0.1:{port}/twitch/vod/{video_id}/master.m3u8"
+    ))
+}
+
+async fn handle_request(
+    app_handle: AppHandle,
+    request: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let origin = request
+        .headers()
+        .get(ORIGIN)
+        .filter(|origin| is_allowed_origin(origin.to_str().unwrap_or_default()))
+        .cloned();
+
+    let mut response = route_request(app_handle, request).await;
+
+    // The webview origin is not the server's, so the player needs CORS to read the responses,
+    // other sites open in a browser can't read them, external players don't send an origin
+    if let Some(origin) = origin {
+        let headers = response.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        headers.insert(VARY, HeaderValue::from_static("Origin"));
+    }
+
+    Ok(response)
+}
+
+fn is_allowed_origin(origin: &str) -> bool {
+    WEBVIEW_ORIGINS.contains(&origin) || (cfg!(debug_assertions) && origin == DEV_ORIGIN)
+}
+
+async fn route_request(app_handle: AppHandle, request: Request<Incoming>) -> Response<Full<Bytes>> {
+    if request.method() != Method::GET {
+        return error_response(StatusCode::METHOD_NOT_ALLOWED, "Only GET is supported");
+    }
+
+    let path: Vec<String> = request
+        .uri()
+        .path()
+        .trim_start_matches('/')
+        .split('/')
+        .map(|part| urlencoding::decode(part).map_or_else(|_| part.to_string(), |p| p.into_owned()))
+        .collect();
+
+    let query = request.uri().query().unwrap_or_default();
+    let proxy_segments = query_param(query, "segments").is_some_and(|value| value == "true");
+    let dvr = query_param(query, "dvr").is_some_and(|value| value == "true");
+
+    let response = match path
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>()
+        .as_slice()
+    {
+        ["twitch", "vod", video_id, "master.m3u8"] => {
+            let quality = query_param(query, "quality");
+
+            serve_vod_master_playlist(video_id, quality, proxy_segments).await
+        }
+        ["twitch", "vod", "media.m3u8"] => {
+            let Some(url) = query_param(query, "url") else {
+                return error_response(StatusCode::BAD_REQUEST, "Missing url");
+            };
+
+            serve_vod_media_playlist(&url, proxy_segments).await
+        }
+        ["twitch", window_label, username, "master.m3u8"] => {
+            let quality = query_param(query, "quality");
+
+            serve_master_playlist(
+                &app_handle,
+                window_label,
+                username,
+                quality,
+                proxy_segments,
+                dvr,
+            )
+            .await
+        }
+        ["twitch", window_label, username, "media.m3u8"] => {
+            let Some(url) = query_param(query, "url") else {
+                return error_response(StatusCode::BAD_REQUEST, "Missing url");
+            };
+
+            serve_media_playlist(
+                &app_handle,
+                window_label,
+                username,
+                &url,
+                proxy_segments,
+                dvr,
+            )
+            .await
+        }
+        ["twitch", "segment"] => {
+            let Some(url) = query_param(query, "url") else {
+                return error_response(StatusCode::BAD_REQUEST, "Missing url");
+            };
+
+            serve_segment(&url).await
+        }
+        ["twitch", "dvr", id, segment] => {
+            let (Ok(id), Some(Ok(sequence))) = (
+                id.parse::<u64>(),
+                segment.strip_suffix(".ts").map(str::parse::<u64>),
+            ) else {
+                return error_response(StatusCode::NOT_FOUND, "Not found");
+            };
+
+            serve_dvr_segment(id, sequence).await
+        }
+        _ => return error_response(StatusCode::NOT_FOUND, "Not found"),
+    };
+
+    match response {
+        Ok(response) => response,
+        Err(err) => {
+            error!("Failed to serve '{}': {err}", request.uri());
+            error_response(StatusCode::BAD_GATEWAY, &err.to_string())
+        }
+    }
+}
+
+/// Only the preferred quality is served if there is one, so the player can't switch to another.
+async fn serve_master_playlist(
+    app_handle: &AppHandle,
+    window_label: &str,
+    username: &str,
+    quality: Option<String>,
+    proxy_segments: bool,
+    dvr: bool,
+) -> Result<Response<Full<Bytes>>> {
+    let mut playlist = proxy::fetch_master_playlist(username, false).await?;
+
+    proxy::reset_stream_state(
+        window_label,
+        username,
+        playlist.variants().cloned().collect(),
+    )
+    .await;
+
+    let quality = match quality {
+        Some(quality) => Some(quality),
+        None => {
+            let state = app_handle.state::<Mutex<AppState>>();
+            let users_db = state.lock().await.users_db.clone().unwrap();
+
+            quality::preferred_quality(&users_db, username).await?
+        }
+    };
+
+    retain_quality(&mut playlist, quality.as_deref());
+    allow_hosts(playlist.variants().map(|variant| variant.uri.as_str())).await;
+
+    let segments = if proxy_segments { "&segments=true" } else { "" };
+    let dvr = if dvr { "&dvr=true" } else { "" };
+    let window_label = urlencoding::encode(window_label);
+    let username = urlencoding::encode(username);
+
+    for entry in &mut playlist.entries {
+        if let MasterEntry::Variant(variant) = entry {
+            variant.uri = format!(
+                "/twitch/{window_label}/{username}/media.m3u8?url={}{segments}{dvr}",
+                urlencoding::encode(&variant.uri)
+            );
+        }
+    }
+
+    Ok(response(
+        StatusCode::OK,
+        PLAYLIST_CONTENT_TYPE,
+        playlist.to_string(),
+    ))
+}
+
+async fn serve_media_playlist(
+    app_handle: &AppHandle,
+    window_label: &str,
+    username: &str,
+    url: &str,
+    proxy_segments: bool,
+    dvr: bool,
+) -> Result<Response<Full<Bytes>>> {
+    check_allowed_host(url).await?;
+
+    let mut playlist =
+        proxy::proxy_media_playlist(app_handle, window_label, username, url, dvr).await?;
+
+    if proxy_segments {
+        allow_hosts(playlist.segments.iter().map(|segment| segment.uri.as_str())).await;
+
+        // DVR segments are already served locally
+        for segment in &mut playlist.segments {
+            if !segment.uri.starts_with('/') {
+                segment.uri = format!("/twitch/segment?url={}", urlencoding::encode(&segment.uri));
+            }
+        }
+    }
+
+    Ok(response(
+        StatusCode::OK,
+        PLAYLIST_CONTENT_TYPE,
+        playlist.to_string(),
+    ))
+}
+
+async fn serve_segment(url: &str) -> Result<Response<Full<Bytes>>> {
+    check_allowed_host(url).await?;
+
+    let bytes = proxy::fetch_segment(url).await?;
+
+    Ok(response(StatusCode::OK, SEGMENT_CONTENT_TYPE, bytes))
+}
+
+async fn serve_vod_master_playlist(
+    video_id: &str,
+    quality: Option<String>,
+    proxy_segments: bool,
+) -> Result<Response<Full<Bytes>>> {
+    let mut playlist = vod::fetch_master_playlist(video_id).await?;
+
+    retain_quality(&mut playlist, quality.as_deref());
+    allow_hosts(playlist.variants().map(|variant| variant.uri.as_str())).await;
+
+    let segments = if proxy_segments { "&segments=true" } else { "" };
+
+    for entry in &mut playlist.entries {
+        if let MasterEntry::Variant(variant) = entry {
+            variant.uri = format!(
+                "/twitch/vod/media.m3u8?url={}{segments}",
+                urlencoding::encode(&variant.uri)
+            );
+        }
+    }
+
+    Ok(response(
+        StatusCode::OK,
+        PLAYLIST_CONTENT_TYPE,
+        playlist.to_string(),
+    ))
+}
+
+async fn serve_vod_media_playlist(
+    url: &str,
+    proxy_segments: bool,
+) -> Result<Response<Full<Bytes>>> {
+    check_allowed_host(url).await?;
+
+    let mut playlist = vod::fetch_media_playlist(url).await?;
+
+    if proxy_segments {
+        allow_hosts(playlist.segments.iter().map(|segment| segment.uri.as_str())).await;
+
+        for segment in &mut playlist.segments {
+            segment.uri = format!("/twitch/segment?url={}", urlencoding::encode(&segment.uri));
+        }
+    }
+
+    Ok(response(
+        StatusCode::OK,
+        PLAYLIST_CONTENT_TYPE,
+        playlist.to_string(),
+    ))
+}
+
+/// Only keeps the variant of `quality`, or every variant but audio only if there is none.
+fn retain_quality(playlist: &mut MasterPlaylist, quality: Option<&str>) {
+    if quality.is_some() {
+        if let Some(uri) = quality::select_variant(playlist, quality).map(|v| v.uri.clone()) {
+            playlist.retain_variants(|variant| variant.uri == uri);
+        }
+    } else {
+        // Players may switch to it on their own, it has to be asked for
+        playlist.retain_variants(|variant| !variant.is_audio_only());
+    }
+}
+
+async fn serve_dvr_segment(id: u64, sequence: u64) -> Result<Response<Full<Bytes>>> {
+    let bytes = dvr::fetch_segment(id, sequence).await?;
+
+    Ok(response(StatusCode::OK, SEGMENT_CONTENT_TYPE, bytes))
+}
+
+/// Allows proxying from the hosts of `urls`, called with the URLs of the playlists served before rewriting them.
+async fn allow_hosts(urls: impl Iterator<Item = &str>) {
+    let mut allowed_hosts = ALLOWED_HOSTS.lock().await;
+
+    allowed_hosts.extend(urls.filter_map(url_host).map(String::from));
+}
+
+async fn check_allowed_host(url: &str) -> Result<()> {
+    let host = url_host(url).ok_or_else(|| anyhow!("Only https URLs can be proxied"))?;
+
+    if !ALLOWED_HOSTS.lock().await.contains(host) {
+        return Err(anyhow!("Host '{host}' is not allowed"));
+    }
+
+    Ok(())
+}
+
+fn url_host(url: &str) -> Option<&str> {
+    url.strip_prefix("https://")
+        .and_then(|rest| rest.split(['/', '?', ':']).next())
+        .filter(|host| !host.is_empty())
+}
+
+fn query_param(query: &str, key: &str) -> Option<String> {
+    query.split('&').find_map(|pair| {
+        let (k, v) = pair.split_once('=')?;
+
+        if k != key {
+            return None;
+        }
+
+        urlencoding::decode(v).ok().map(|v| v.into_owned())
+    })
+}
+
+fn response(
+    status: StatusCode,
+    content_type: &str,
+    body: impl Into<Bytes>,
+) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+    headers.insert(CACHE_CONTROL, "no-cache".parse().unwrap());
+
+    response
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    response(status, "text/plain", message.to_string())
+}
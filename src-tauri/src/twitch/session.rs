@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use log::{error, warn};
+use serde::Serialize;
+use sqlx::{Pool, Row, Sqlite};
+use tauri::{async_runtime::Mutex, State};
+
+use crate::{util, AppState};
+
+use super::{
+    main,
+    query::{ArchiveVideosData, GraphQLQuery},
+    stream::LiveNow,
+};
+
+/// Sessions returned when no limit is provided.
+const DEFAULT_SESSIONS_LIMIT: i64 = 50;
+
+/// How many past broadcasts are fetched to link sessions to their VODs.
+const ARCHIVE_VIDEOS_COUNT: u32 = 100;
+
+/// Maximum difference, in seconds, between the start of a session and the creation of its VOD.
+const VOD_MATCH_TOLERANCE: i64 = 15 * 60;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamSession {
+    pub id: i64,
+    pub username: String,
+    /// Unix timestamp.
+    pub started_at: i64,
+    /// Unix timestamp of the last feed refresh that saw the stream live.
+    pub last_seen_at: i64,
+    /// Unix timestamp, `None` while the stream is still live.
+    pub ended_at: Option<i64>,
+    /// Title and category over the session, oldest first.
+    pub changes: Vec<StreamSessionChange>,
+    /// Past broadcast of the session, if the channel keeps them.
+    pub vod_id: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamSessionChange {
+    pub changed_at: i64,
+    pub title: Option<String>,
+    pub game: Option<String>,
+}
+
+/// Returns the latest stream sessions of a user, most recent first.
+#[tauri::command]
+pub async fn get_stream_sessions(
+    state: State<'_, Mutex<AppState>>,
+    username: String,
+    limit: Option<i64>,
+) -> Result<Vec<StreamSession>, String> {
+    let mut sessions = {
+        let state = state.lock().await;
+        let feeds_db = state.feeds_db.as_ref().unwrap();
+
+        match query_sessions(feeds_db, &username, limit.unwrap_or(DEFAULT_SESSIONS_LIMIT)).await {
+            Ok(sessions) => sessions,
+            Err(err) => {
+                return Err(format!("Failed to fetch stream sessions: {err}"));
+            }
+        }
+    };
+
+    if sessions.is_empty() {
+        return Ok(sessions);
+    }
+
+    // Sessions are still useful without their VODs
+    match fetch_archive_videos(&username).await {
+        Ok(videos) => link_vods(&mut sessions, &videos),
+        Err(err) => error!("Failed to fetch past broadcasts of '{username}': {err}"),
+    }
+
+    Ok(sessions)
+}
+
+/// Updates the sessions of the users checked in a feed refresh.
+///
+/// Live users get their session opened or extended, with a change recorded if the title or category changed,
+/// `offline` users get their open session closed at the last time they were seen live.
+pub async fn update_sessions(
+    feeds_db: &Pool<Sqlite>,
+    live_now: &HashMap<String, LiveNow>,
+    offline: &[&String],
+) -> Result<()> {
+    let now = util::unix_timestamp();
+
+    for (username, live) in live_now {
+        let Some(started_at) = util::parse_timestamp(&live.started_at) else {
+            warn!(
+                "Invalid stream start '{}' for '{username}'",
+                live.started_at
+            );
+            continue;
+        };
+
+        let query =
+            "SELECT id, started_at FROM stream_sessions WHERE username = ? AND ended_at IS NULL";
+
+        let open_session = sqlx::query(query)
+            .bind(username)
+            .fetch_optional(feeds_db)
+            .await?;
+
+        let session_id = match open_session {
+            Some(row) if row.try_get::<i64, _>("started_at")? == started_at => {
+                let id: i64 = row.try_get("id")?;
+
+                let query = "UPDATE stream_sessions SET last_seen_at = ? WHERE id = ?";
+                sqlx::query(query)
+                    .bind(now)
+                    .bind(id)
+                    .execute(feeds_db)
+                    .await?;
+
+                id
+            }
+            open_session => {
+                // The stream restarted between refreshes, the previous session ended
+                if let Some(row) = open_session {
+                    let id: i64 = row.try_get("id")?;
+                    close_session(feeds_db, id).await?;
+                }
+
+                let query = "INSERT INTO stream_sessions (username, started_at, last_seen_at) VALUES (?, ?, ?) ON CONFLICT (username, started_at) DO UPDATE SET last_seen_at = ?, ended_at = NULL RETURNING id";
+
+                sqlx::query(query)
+                    .bind(username)
+                    .bind(started_at)
+                    .bind(now)
+                    .bind(now)
+                    .fetch_one(feeds_db)
+                    .await?
+                    .try_get("id")?
+            }
+        };
+
+        let query = "SELECT title, game FROM stream_session_changes WHERE session_id = ? ORDER BY id DESC LIMIT 1";
+
+        let last_change = sqlx::query(query)
+            .bind(session_id)
+            .fetch_optional(feeds_db)
+            .await?;
+
+        let changed = match last_change {
+            Some(row) => {
+                row.try_get::<Option<String>, _>("title")?.as_deref() != Some(&live.title)
+                    || row.try_get::<Option<String>, _>("game")? != live.game
+            }
+            None => true,
+        };
+
+        if changed {
+            let query = "INSERT INTO stream_session_changes (session_id, changed_at, title, game) VALUES (?, ?, ?, ?)";
+
+            sqlx::query(query)
+                .bind(session_id)
+                .bind(now)
+                .bind(&live.title)
+                .bind(&live.game)
+                .execute(feeds_db)
+                .await?;
+        }
+    }
+
+    for username in offline {
+        let query = "SELECT id FROM stream_sessions WHERE username = ? AND ended_at IS NULL";
+
+        let rows = sqlx::query(query)
+            .bind(username)
+            .fetch_all(feeds_db)
+            .await?;
+
+        for row in rows {
+            close_session(feeds_db, row.try_get("id")?).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The exact end is unknown, the last time the stream was seen live is used instead.
+async fn close_session(feeds_db: &Pool<Sqlite>, id: i64) -> Result<()> {
+    let query = "UPDATE stream_sessions SET ended_at = last_seen_at WHERE id = ?";
+
+    sqlx::query(query).bind(id).execute(feeds_db).await?;
+
+    Ok(())
+}
+
+async fn query_sessions(
+    feeds_db: &Pool<Sqlite>,
+    username: &str,
+    limit: i64,
+) -> Result<Vec<StreamSession>> {
+    let query = "SELECT id, username, started_at, last_seen_at, ended_at FROM stream_sessions WHERE username = ? ORDER BY last_seen_at DESC LIMIT ?";
+
+    let rows = sqlx::query(query)
+        .bind(username)
+        .bind(limit)
+        .fetch_all(feeds_db)
+        .await?;
+
+    let mut sessions = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let id: i64 = row.try_get("id")?;
+
+        let query = "SELECT changed_at, title, game FROM stream_session_changes WHERE session_id = ? ORDER BY id";
+
+        let change_rows = sqlx::query(query).bind(id).fetch_all(feeds_db).await?;
+
+        let mut changes = Vec::with_capacity(change_rows.len());
+
+        for change_row in change_rows {
+            changes.push(StreamSessionChange {
+                changed_at: change_row.try_get("changed_at")?,
+                title: change_row.try_get("title")?,
+                game: change_row.try_get("game")?,
+            });
+        }
+
+        sessions.push(StreamSession {
+            id,
+            username: row.try_get("username")?,
+            started_at: row.try_get("started_at")?,
+            last_seen_at: row.try_get("last_seen_at")?,
+            ended_at: row.try_get("ended_at")?,
+            changes,
+            vod_id: None,
+        });
+    }
+
+    Ok(sessions)
+}
+
+/// Returns the IDs and creation dates of the user's latest past broadcasts.
+async fn fetch_archive_videos(username: &str) -> Result<Vec<(String, i64)>> {
+    let gql = GraphQLQuery::archive_videos(username, ARCHIVE_VIDEOS_COUNT);
+
+    let response: ArchiveVideosData = main::send_query(gql).await?;
+
+    let videos = response
+        .user
+        .and_then(|user| user.videos)
+        .map(|videos| videos.edges)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|edge| {
+            let created_at = util::parse_timestamp(&edge.node.created_at)?;
+            Some((edge.node.id, created_at))
+        })
+        .collect();
+
+    Ok(videos)
+}
+
+/// VODs are created when the stream starts, the closest one to the start of each session is used.
+fn link_vods(sessions: &mut [StreamSession], videos: &[(String, i64)]) {
+    for session in sessions {
+        session.vod_id = videos
+            .iter()
+            .map(|(id, created_at)| (id, (created_at - session.started_at).abs()))
+            .filter(|(_, difference)| *difference <= VOD_MATCH_TOLERANCE)
+            .min_by_key(|(_, difference)| *difference)
+            .map(|(id, _)| id.clone());
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::{Pool, Row, Sqlite};
+use tauri::{async_runtime::Mutex, State};
+
+use crate::AppState;
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AdBreak {
+    pub username: String,
+    /// Unix timestamps in seconds.
+    pub started_at: i64,
+    pub ended_at: i64,
+    /// Duration in seconds.
+    pub duration: i64,
+    /// If the backup stream was served for the whole ad break.
+    pub backup_succeeded: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdBlockStats {
+    pub total_ad_breaks: i64,
+    pub total_duration: i64,
+    pub backup_succeeded: i64,
+    pub ad_breaks: Vec<AdBreak>,
+}
+
+pub async fn save_ad_break(db: &Pool<Sqlite>, ad_break: &AdBreak) -> Result<()> {
+    let query = "INSERT INTO ad_breaks (username, started_at, ended_at, duration, backup_succeeded) VALUES (?, ?, ?, ?, ?)";
+
+    sqlx::query(query)
+        .bind(&ad_break.username)
+        .bind(ad_break.started_at)
+        .bind(ad_break.ended_at)
+        .bind(ad_break.duration)
+        .bind(ad_break.backup_succeeded)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the recorded ad breaks, optionally only from one user, newest first.
+#[tauri::command]
+pub async fn get_ad_breaks(
+    state: State<'_, Mutex<AppState>>,
+    username: Option<String>,
+) -> Result<AdBlockStats, String> {
+    let state = state.lock().await;
+    let feeds_db = state.feeds_db.as_ref().unwrap();
+
+    let query = "SELECT username, started_at, ended_at, duration, backup_succeeded FROM ad_breaks WHERE ? IS NULL OR username = ? ORDER BY started_at DESC";
+
+    let rows = match sqlx::query(query)
+        .bind(&username)
+        .bind(&username)
+        .fetch_all(feeds_db)
+        .await
+    {
+        Ok(rows) => rows,
+        Err(err) => {
+            return Err(format!("Failed to fetch ad breaks: {err}"));
+        }
+    };
+
+    let mut ad_breaks: Vec<AdBreak> = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let ad_break = AdBreak {
+            username: row.try_get("username").map_err(|e| e.to_string())?,
+            started_at: row.try_get("started_at").map_err(|e| e.to_string())?,
+            ended_at: row.try_get("ended_at").map_err(|e| e.to_string())?,
+            duration: row.try_get("duration").map_err(|e| e.to_string())?,
+            backup_succeeded: row.try_get("backup_succeeded").map_err(|e| e.to_string())?,
+        };
+
+        ad_breaks.push(ad_break);
+    }
+
+    Ok(AdBlockStats {
+        total_ad_breaks: ad_breaks.len() as i64,
+        total_duration: ad_breaks.iter().map(|ad_break| ad_break.duration).sum(),
+        backup_succeeded: ad_breaks
+            .iter()
+            .filter(|ad_break| ad_break.backup_succeeded)
+            .count() as i64,
+        ad_breaks,
+    })
+}
@@ -1,60 +1,135 @@
 use std::collections::HashMap;
 
 use anyhow::{anyhow, Result};
+use futures_util::{stream, StreamExt};
+use log::warn;
 use serde::Serialize;
 
 use crate::util;
 
 use super::{
     main,
-    query::{GraphQLQuery, GraphQLResponse, UseLiveQuery, UseLiveResponse},
+    query::{GraphQLError, GraphQLQuery, GraphQLResponseStream, LiveNowData, StreamPlaybackData},
 };
 
 const USHER_API: &str = "https://usher.ttvnw.net/api/channel/hls";
 
+/// Twitch rejects batches with more queries than this.
+const LIVE_NOW_BATCH_SIZE: usize = 35;
+const MAX_CONCURRENT_BATCHES: usize = 4;
+
 #[derive(Serialize, Debug)]
 pub struct LiveNow {
     pub username: String,
     pub started_at: String,
+    pub title: String,
+    pub game: Option<String>,
+    pub viewers: i64,
+    pub tags: Vec<String>,
+    /// URL of the stream preview.
+    pub thumbnail: String,
 }
 
-pub async fn fetch_live_now(usernames: Vec<String>) -> Result<HashMap<String, LiveNow>> {
-    let mut query: Vec<UseLiveQuery> = Vec::new();
-
-    for username in usernames {
-        if username.is_empty() {
-            continue;
+impl LiveNow {
+    pub fn from_stream(username: String, stream: GraphQLResponseStream) -> Self {
+        Self {
+            username,
+            started_at: stream.created_at.unwrap_or_default(),
+            title: stream.title.unwrap_or_default(),
+            game: stream.game.map(|game| game.name),
+            viewers: stream.viewers_count.unwrap_or_default(),
+            tags: stream
+                .freeform_tags
+                .unwrap_or_default()
+                .into_iter()
+                .map(|tag| tag.name)
+                .collect(),
+            thumbnail: stream.preview_image_url.unwrap_or_default(),
         }
-
-        query.push(UseLiveQuery::new(&username));
     }
+}
 
-    let response: Vec<UseLiveResponse> = match main::send_query(query).await {
-        Ok(data) => data,
-        Err(err) => {
-            return Err(anyhow!("Failed to fetch UseLive: {err}"));
-        }
-    };
+/// Fetches which users are live, the users are split in batches of [`LIVE_NOW_BATCH_SIZE`],
+/// with up to [`MAX_CONCURRENT_BATCHES`] sent at the same time.
+///
+/// A failed batch doesn't fail the others, the users in it are returned separately so their feed is left as is.
+/// Only fails if every batch fails.
+pub async fn fetch_live_now(
+    usernames: Vec<String>,
+) -> Result<(HashMap<String, LiveNow>, Vec<String>)> {
+    let usernames: Vec<String> = usernames
+        .into_iter()
+        .filter(|username| !username.is_empty())
+        .collect();
+
+    let batches: Vec<Vec<String>> = usernames
+        .chunks(LIVE_NOW_BATCH_SIZE)
+        .map(<[String]>::to_vec)
+        .collect();
+
+    type BatchResult = Result<Vec<Result<LiveNowData, GraphQLError>>, GraphQLError>;
+
+    let results: Vec<(Vec<String>, BatchResult)> = stream::iter(batches)
+        .map(|batch| async move {
+            let queries = batch
+                .iter()
+                .map(|username| GraphQLQuery::live_now(username))
+                .collect();
+
+            let response = main::send_batch(queries).await;
+            (batch, response)
+        })
+        .buffer_unordered(MAX_CONCURRENT_BATCHES)
+        .collect()
+        .await;
 
     let mut live_now: HashMap<String, LiveNow> = HashMap::new();
+    let mut failed: Vec<String> = Vec::new();
+    let mut last_error = None;
+
+    for (batch, response) in results {
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                warn!("Failed to fetch live now for {} users: {err}", batch.len());
+                failed.extend(batch);
+                last_error = Some(err);
+                continue;
+            }
+        };
 
-    for obj in response {
-        if obj.data.user.stream.is_none() {
-            continue;
+        let mut response = response.into_iter();
+
+        for username in batch {
+            // Twitch may return fewer responses than queries, users without one failed
+            let data = match response.next().unwrap_or(Err(GraphQLError::MissingData)) {
+                Ok(data) => data,
+                Err(err) => {
+                    warn!("Failed to fetch live now for '{username}': {err}");
+                    failed.push(username);
+                    last_error = Some(err);
+                    continue;
+                }
+            };
+
+            // Users that don't exist anymore are null
+            let Some(user) = data.user else {
+                continue;
+            };
+
+            let Some(stream) = user.stream else {
+                continue;
+            };
+
+            live_now.insert(user.login.clone(), LiveNow::from_stream(user.login, stream));
         }
+    }
 
-        let stream = obj.data.user.stream.unwrap();
-        let username = obj.data.user.login;
-
-        let live = LiveNow {
-            username: username.clone(),
-            started_at: stream.created_at,
-        };
-
-        live_now.insert(username, live);
+    if let Some(err) = last_error.filter(|_| failed.len() == usernames.len()) {
+        return Err(anyhow!("Failed to fetch live now: {err}"));
     }
 
-    Ok(live_now)
+    Ok((live_now, failed))
 }
 
 #[tauri::command]
@@ -65,14 +140,14 @@ pub async fn fetch_stream_playback(username: &str, backup: bool) -> Result<Strin
 
     let gql = GraphQLQuery::playback_query(username, backup);
 
-    let response: GraphQLResponse = match main::send_query(gql).await {
+    let response: StreamPlaybackData = match main::send_query(gql).await {
         Ok(response) => response,
         Err(err) => {
             return Err(format!("Failed to fetch stream info: {err}"));
         }
     };
 
-    let Some(stream_playback) = response.data.stream_playback_access_token else {
+    let Some(stream_playback) = response.stream_playback_access_token else {
         return Err(String::from("No stream playback access token found"));
     };
 
@@ -90,9 +165,9 @@ fn playlist_url(username: &str, backup: bool, signature: &str, token: &str) -> S
     let random_number = util::random_number(1_000_000, 10_000_000);
 
     if backup {
-        url.push_str(&format!("?platform=ios&supported_codecs=h264&player=twitchweb&fast_bread=true&p={random_number}&sig={signature}&token={token}"));
+        url.push_str(&format!("?platform=ios&supported_codecs=h264&allow_audio_only=true&player=twitchweb&fast_bread=true&p={random_number}&sig={signature}&token={token}"));
     } else {
-        url.push_str(&format!("?platform=web&supported_codecs=av1,h265,h264&allow_source=true&player=twitchweb&fast_bread=true&p={random_number}&sig={signature}&token={token}"));
+        url.push_str(&format!("?platform=web&supported_codecs=av1,h265,h264&allow_source=true&allow_audio_only=true&player=twitchweb&fast_bread=true&p={random_number}&sig={signature}&token={token}"));
     }
 
     url.to_string()
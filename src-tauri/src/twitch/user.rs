@@ -1,35 +1,196 @@
 use std::collections::HashMap;
 
 use anyhow::{anyhow, Result};
-use log::error;
+use futures_util::{stream, StreamExt};
+use log::{error, info};
+use serde::Serialize;
+use sqlx::{Pool, Row, Sqlite};
+use tauri::{async_runtime::Mutex, AppHandle, Emitter, State};
 
 use crate::{
     user::{Platform, User},
-    util,
+    util, AppState,
 };
 
 use super::{
     emote::{self, Emote, TWITCH_EMOTES_CDN},
     main,
-    query::{GraphQLQuery, GraphQLResponse},
+    query::{FollowsData, FullUserData, GraphQLQuery},
 };
 
+/// Follows requested per page, Twitch doesn't return more than this.
+const FOLLOWS_PAGE_SIZE: u32 = 100;
+
+/// Users fetched at the same time when importing, each one also fetches its 7tv and BetterTTV emotes.
+const MAX_CONCURRENT_IMPORTS: usize = 4;
+
+/// Sent with the `import_progress` event after every followed channel is processed.
+#[derive(Serialize, Clone)]
+pub struct ImportProgress {
+    pub username: String,
+    /// Channels processed so far, including skipped and failed ones.
+    pub processed: usize,
+    pub total: usize,
+    pub imported: usize,
+    /// Channels that were already added.
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// Imports every channel a Twitch user follows, channels already added are skipped.
+///
+/// Returns how many channels were imported, `import_progress` is emitted as they are processed.
+#[tauri::command]
+pub async fn import_follows(
+    app_handle: AppHandle,
+    state: State<'_, Mutex<AppState>>,
+    username: String,
+) -> Result<usize, String> {
+    let username = username.trim().to_lowercase();
+
+    if username.is_empty() {
+        return Err(String::from("No username provided"));
+    }
+
+    // The import can take a while, the lock is not held so the app keeps working
+    let (users_db, emotes_db) = {
+        let state = state.lock().await;
+        (
+            state.users_db.clone().unwrap(),
+            state.emotes_db.clone().unwrap(),
+        )
+    };
+
+    let follows = match fetch_follows(&username).await {
+        Ok(follows) => follows,
+        Err(err) => {
+            return Err(format!("Failed to fetch follows of '{username}': {err}"));
+        }
+    };
+
+    let rows = match sqlx::query("SELECT username FROM twitch")
+        .fetch_all(&users_db)
+        .await
+    {
+        Ok(rows) => rows,
+        Err(err) => {
+            return Err(format!("Failed to fetch usernames from database: {err}"));
+        }
+    };
+
+    let mut saved_usernames: Vec<String> = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        saved_usernames.push(row.try_get("username").map_err(|e| e.to_string())?);
+    }
+
+    let total = follows.len();
+
+    let (skipped, new_follows): (Vec<String>, Vec<String>) = follows
+        .into_iter()
+        .partition(|follow| saved_usernames.contains(follow));
+
+    let mut progress = ImportProgress {
+        username: String::new(),
+        processed: skipped.len(),
+        total,
+        imported: 0,
+        skipped: skipped.len(),
+        failed: 0,
+    };
+
+    info!(
+        "Importing {} channels followed by '{username}', {} already added",
+        new_follows.len(),
+        skipped.len()
+    );
+
+    let mut results = stream::iter(new_follows)
+        .map(|follow| async move {
+            let user = fetch_user(&follow).await;
+            (follow, user)
+        })
+        .buffer_unordered(MAX_CONCURRENT_IMPORTS);
+
+    while let Some((follow, user)) = results.next().await {
+        let result = match user {
+            Ok((user, emotes)) => save_user(&users_db, &emotes_db, &user, emotes).await,
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(()) => progress.imported += 1,
+            Err(err) => {
+                error!("Failed to import channel '{follow}': {err}");
+                progress.failed += 1;
+            }
+        }
+
+        progress.processed += 1;
+        progress.username = follow;
+
+        if let Err(err) = app_handle.emit("import_progress", &progress) {
+            error!("Failed to emit 'import_progress' event: {err}");
+        }
+    }
+
+    if let Err(err) = app_handle.emit("updated_users", Platform::Twitch) {
+        error!("Failed to emit 'updated_users' event: {err}");
+    }
+
+    Ok(progress.imported)
+}
+
+/// Returns the logins of every channel a user follows.
+async fn fetch_follows(username: &str) -> Result<Vec<String>> {
+    let mut follows = Vec::new();
+    let mut cursor: Option<String> = None;
+
+    loop {
+        let gql = GraphQLQuery::follows(username, FOLLOWS_PAGE_SIZE, cursor.as_deref());
+        let response: FollowsData = main::send_query(gql).await?;
+
+        let Some(user) = response.user else {
+            return Err(anyhow!("User '{username}' not found"));
+        };
+
+        let Some(page) = user.follows else {
+            return Err(anyhow!("Follows of '{username}' are not available"));
+        };
+
+        let has_next_page = page.page_info.has_next_page;
+        cursor = page.edges.last().map(|edge| edge.cursor.clone());
+
+        follows.extend(
+            page.edges
+                .into_iter()
+                .filter_map(|edge| edge.node.map(|node| node.login)),
+        );
+
+        if !has_next_page || cursor.is_none() {
+            break;
+        }
+    }
+
+    Ok(follows)
+}
+
 pub async fn fetch_user(username: &str) -> Result<(User, HashMap<String, Emote>)> {
     let gql = GraphQLQuery::full_user(username);
 
-    let response: GraphQLResponse = match main::send_query(gql).await {
+    let response: FullUserData = match main::send_query(gql).await {
         Ok(response) => response,
         Err(err) => {
             return Err(anyhow!("Failed to fetch user '{username}': {err}"));
         }
     };
 
-    let Some(user) = response.data.user else {
+    let Some(user) = response.user else {
         return Err(anyhow!("User '{username}' not found"));
     };
 
     let mut user_emotes: HashMap<String, Emote> = HashMap::new();
-    for product in user.subscription_products.unwrap() {
+    for product in user.subscription_products.unwrap_or_default() {
         for emote in product.emotes {
             let name = emote.token;
             let url = format!("{TWITCH_EMOTES_CDN}/{}/default/dark/1.0", emote.id);
@@ -45,7 +206,7 @@ pub async fn fetch_user(username: &str) -> Result<(User, HashMap<String, Emote>)
         }
     }
 
-    let user_id = user.id.unwrap();
+    let user_id = user.id;
 
     let seventv_emotes = match emote::fetch_7tv_emotes(&user_id).await {
         Ok(emotes) => emotes,
@@ -77,3 +238,27 @@ pub async fn fetch_user(username: &str) -> Result<(User, HashMap<String, Emote>)
 
     Ok((user, user_emotes))
 }
+
+/// Saves a user and their emotes, failing to save the emotes is not fatal.
+pub async fn save_user(
+    users_db: &Pool<Sqlite>,
+    emotes_db: &Pool<Sqlite>,
+    user: &User,
+    emotes: HashMap<String, Emote>,
+) -> Result<()> {
+    if let Err(err) = emote::update_user_emotes(emotes_db, &user.username, emotes).await {
+        error!("Failed to save emotes for user '{}': {err}", user.username);
+    }
+
+    let query = "INSERT INTO twitch (id, username, avatar) VALUES (?, ?, ?) ON CONFLICT (username) DO UPDATE SET avatar = ?";
+
+    sqlx::query(query)
+        .bind(&user.id)
+        .bind(&user.username)
+        .bind(&user.avatar)
+        .bind(&user.avatar)
+        .execute(users_db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use anyhow::{anyhow, Result};
+
+use crate::util;
+
+use super::{
+    m3u8::{MasterPlaylist, MediaPlaylist, Playlist},
+    main, proxy,
+    query::{GraphQLQuery, VideoPlaybackData},
+};
+
+const USHER_VOD_API: &str = "https://usher.ttvnw.net/vod";
+
+#[tauri::command]
+pub async fn fetch_vod_playback(video_id: &str) -> Result<String, String> {
+    if video_id.is_empty() {
+        return Err(String::from("No video ID provided"));
+    }
+
+    if !video_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid video ID '{video_id}'"));
+    }
+
+    let gql = GraphQLQuery::vod_playback_query(video_id);
+
+    let response: VideoPlaybackData = match main::send_query(gql).await {
+        Ok(response) => response,
+        Err(err) => {
+            return Err(format!("Failed to fetch video info: {err}"));
+        }
+    };
+
+    let Some(video_playback) = response.video_playback_access_token else {
+        return Err(String::from("No video playback access token found"));
+    };
+
+    Ok(playlist_url(
+        video_id,
+        &video_playback.signature,
+        &video_playback.value,
+    ))
+}
+
+pub async fn fetch_master_playlist(video_id: &str) -> Result<MasterPlaylist> {
+    let url = match fetch_vod_playback(video_id).await {
+        Ok(url) => url,
+        Err(err) => {
+            return Err(anyhow!("Failed to fetch video playback: {err}"));
+        }
+    };
+
+    match Playlist::parse(&proxy::fetch_playlist_text(&url).await?)? {
+        Playlist::Master(playlist) => Ok(playlist),
+        Playlist::Media(_) => Err(anyhow!("Expected a master playlist, got a media playlist")),
+    }
+}
+
+/// Fetches a media playlist of a VOD, segment URIs are relative to the playlist so they are made absolute,
+/// otherwise they would point to the local server.
+pub async fn fetch_media_playlist(url: &str) -> Result<MediaPlaylist> {
+    let mut playlist = proxy::fetch_media_playlist(url).await?;
+
+    let path = url.split('?').next().unwrap_or(url);
+    let base = &path[..path.rfind('/').map_or(path.len(), |i| i + 1)];
+
+    for segment in &mut playlist.segments {
+        if !segment.uri.starts_with("https://") {
+            segment.uri = format!("{base}{}", segment.uri);
+        }
+    }
+
+    Ok(playlist)
+}
+
+fn playlist_url(video_id: &str, signature: &str, token: &str) -> String {
+    let random_number = util::random_number(1_000_000, 10_000_000);
+
+    format!(
+        "{USHER_VOD_API}/{video_id}.m3u8?platform=web&supported_codecs=av1,h265,h264&allow_source=true&allow_audio_only=true&player=twitchweb&p={random_number}&sig={signature}&token={}",
+        urlencoding::encode(token)
+    )
+}
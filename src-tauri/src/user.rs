@@ -77,18 +77,7 @@ pub async fn add_user(
             }
         };
 
-        if let Err(err) = twitch::emote::update_user_emotes(emotes_db, &username, emotes).await {
-            error!("Failed to save emotes for user '{username}': {err}");
-        }
-
-        let query = "INSERT INTO twitch (id, username, avatar) VALUES (?, ?, ?) ON CONFLICT (username) DO UPDATE SET avatar = ?";
-
-        sqlx::query(query)
-            .bind(&user.id)
-            .bind(&user.username)
-            .bind(&user.avatar)
-            .bind(&user.avatar)
-            .execute(users_db)
+        twitch::user::save_user(users_db, emotes_db, &user, emotes)
             .await
             .map_err(|e| e.to_string())?;
     }
@@ -135,6 +124,30 @@ pub async fn remove_user(
     if platform == Platform::Twitch {
         let query = "DELETE FROM twitch WHERE username = ?";
 
+        sqlx::query(query)
+            .bind(&username)
+            .execute(users_db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let query = "DELETE FROM twitch_auto_record WHERE username = ?";
+
+        sqlx::query(query)
+            .bind(&username)
+            .execute(users_db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let query = "DELETE FROM twitch_quality WHERE username = ?";
+
+        sqlx::query(query)
+            .bind(&username)
+            .execute(users_db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let query = "DELETE FROM twitch_notifications WHERE username = ?";
+
         sqlx::query(query)
             .bind(&username)
             .execute(users_db)
@@ -143,6 +156,14 @@ pub async fn remove_user(
 
         let query = "DELETE FROM twitch WHERE username = ?";
 
+        sqlx::query(query)
+            .bind(&username)
+            .execute(feeds_db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let query = "DELETE FROM twitch_checked WHERE username = ?";
+
         sqlx::query(query)
             .bind(&username)
             .execute(feeds_db)
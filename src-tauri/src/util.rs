@@ -14,6 +14,61 @@ pub fn random_number(start: u32, end: u32) -> u32 {
     start + (nanos % (end - start))
 }
 
+pub fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as i64
+}
+
+/// Parses an RFC 3339 date like `2025-01-01T12:00:00Z` or `2025-01-01T12:00:00.123+02:00` into a unix timestamp in seconds.
+pub fn parse_timestamp(date: &str) -> Option<i64> {
+    let (day, time) = date.split_once('T')?;
+
+    let mut day_parts = day.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (
+        day_parts.next()?.ok()?,
+        day_parts.next()?.ok()?,
+        day_parts.next()?.ok()?,
+    );
+
+    let (time, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(i) => time.split_at(i),
+        None => (time, ""),
+    };
+
+    let mut time_parts = time.splitn(3, ':');
+    let hours: i64 = time_parts.next()?.parse().ok()?;
+    let minutes: i64 = time_parts.next()?.parse().ok()?;
+    // Fractional seconds are ignored
+    let seconds: i64 = time_parts.next()?.split('.').next()?.parse().ok()?;
+
+    let offset_seconds = match offset.strip_prefix(['+', '-']) {
+        Some(offset_time) => {
+            let (offset_hours, offset_minutes) = offset_time.split_once(':')?;
+            let total =
+                offset_hours.parse::<i64>().ok()? * 3600 + offset_minutes.parse::<i64>().ok()? * 60;
+
+            if offset.starts_with('-') {
+                -total
+            } else {
+                total
+            }
+        }
+        None => 0,
+    };
+
+    // Days from the civil date, https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86_400 + hours * 3600 + minutes * 60 + seconds - offset_seconds)
+}
+
 pub async fn download_image(url: &str) -> Result<Vec<u8>> {
     if url.is_empty() {
         return Ok(Vec::new());
@@ -30,3 +85,64 @@ pub async fn download_image(url: &str) -> Result<Vec<u8>> {
 
     Ok(bytes.to_vec())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_utc() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("2025-01-01T12:00:00Z"), Some(1_735_732_800));
+        assert_eq!(parse_timestamp("2025-01-01T12:00:00z"), Some(1_735_732_800));
+    }
+
+    #[test]
+    fn parses_offsets() {
+        assert_eq!(
+            parse_timestamp("2025-01-01T14:00:00+02:00"),
+            Some(1_735_732_800)
+        );
+        assert_eq!(
+            parse_timestamp("2025-01-01T06:30:00-05:30"),
+            Some(1_735_732_800)
+        );
+        assert_eq!(
+            parse_timestamp("2025-01-01T12:00:00+00:00"),
+            Some(1_735_732_800)
+        );
+    }
+
+    #[test]
+    fn ignores_fractional_seconds() {
+        assert_eq!(
+            parse_timestamp("2025-01-01T12:00:00.999Z"),
+            Some(1_735_732_800)
+        );
+        assert_eq!(
+            parse_timestamp("2025-01-01T14:00:00.123456+02:00"),
+            Some(1_735_732_800)
+        );
+    }
+
+    #[test]
+    fn handles_leap_years() {
+        assert_eq!(parse_timestamp("2024-02-29T00:00:00Z"), Some(1_709_164_800));
+        assert_eq!(parse_timestamp("2024-03-01T00:00:00Z"), Some(1_709_251_200));
+        assert_eq!(parse_timestamp("2000-02-29T00:00:00Z"), Some(951_782_400));
+        // Not a leap year, the 1st of March is the day after the 28th of February
+        assert_eq!(parse_timestamp("2100-02-28T00:00:00Z"), Some(4_107_456_000));
+        assert_eq!(parse_timestamp("2100-03-01T00:00:00Z"), Some(4_107_542_400));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("2025-01-01"), None);
+        assert_eq!(parse_timestamp("2025-01-01 12:00:00Z"), None);
+        assert_eq!(parse_timestamp("2025-01T12:00:00Z"), None);
+        assert_eq!(parse_timestamp("2025-01-01T12:00Z"), None);
+        assert_eq!(parse_timestamp("2025-aa-01T12:00:00Z"), None);
+        assert_eq!(parse_timestamp("2025-01-01T12:00:00+0200"), None);
+    }
+}
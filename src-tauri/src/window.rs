@@ -9,6 +9,8 @@ use tauri::{
 
 lazy_static! {
     static ref WINDOW_ID: Mutex<u64> = Mutex::new(0);
+    static ref TWITCH_VOD_URL_REG: Regex = Regex::new(r"(?:https?:\/\/)?(?:www\.|m\.)?twitch\.tv\/videos\/(\d+)(?:\?(?:.*&)?t=([0-9hms]+))?").unwrap();
+    static ref TWITCH_CLIP_URL_REG: Regex = Regex::new(r"(?:https?:\/\/)?(?:clips\.twitch\.tv\/|(?:www\.|m\.)?twitch\.tv\/[a-zA-Z0-9_]+\/clip\/)([a-zA-Z0-9_-]+)").unwrap();
     static ref TWITCH_URL_REG: Regex = Regex::new(r"(?:https?:\/\/)?(?:www\.)?twitch\.tv\/([a-zA-Z0-9_]+)").unwrap();
     // https://stackoverflow.com/a/37704433
     static ref YOUTUBE_URL_REG: Regex = Regex::new(r#"(?:https?:\/\/)?(?:www\.)?(?:youtube\.com\/(?:[^\/]+\/.+\/|(?:v|embed|shorts|watch)?\??v=|.*[?&]v=)|youtu\.be\/)([^"&?\/\s]{11})"#).unwrap();
@@ -21,16 +23,58 @@ pub fn open_url(app_handle: AppHandle, urls: &[String]) -> Result<()> {
 
     let url = urls.first().unwrap();
 
-    // For Twitch, only streams are supported, so just get the username from the URL
+    // For Twitch, streams, VODs and clips are supported
     if url.starts_with("rt://tw/") || url.starts_with("rt://twitch/") {
-        let username = url
+        let path = url
             .trim_start_matches("rt://tw/")
             .trim_start_matches("rt://twitch/");
-        let url = format!("/streams/watch?username={username}");
+
+        if path.starts_with("videos/") {
+            // Same as on twitch.tv, `t` can be anywhere in the query
+            let twitch_url = format!("twitch.tv/{path}");
+
+            let Some(caps) = TWITCH_VOD_URL_REG.captures(&twitch_url) else {
+                return Err(anyhow!("Invalid video URL '{url}'"));
+            };
+
+            let video_id = caps.get(1).map_or("", |m| m.as_str());
+            let timestamp = caps.get(2).map_or("", |t| t.as_str());
+            open_new_window(app_handle, vod_url(video_id, timestamp));
+            return Ok(());
+        }
+
+        // Both `clip/<slug>` and `<user>/clip/<slug>`, like on twitch.tv
+        let clip = path
+            .strip_prefix("clip/")
+            .or_else(|| path.split_once("/clip/").map(|(_, slug)| slug));
+
+        if let Some(slug) = clip {
+            let url = format!("/streams/clip?slug={slug}");
+            open_new_window(app_handle, url);
+            return Ok(());
+        }
+
+        let url = format!("/streams/watch?username={path}");
         open_new_window(app_handle, url);
         return Ok(());
     }
 
+    if let Some(caps) = TWITCH_VOD_URL_REG.captures(url) {
+        if let Some(m) = caps.get(1) {
+            let timestamp = caps.get(2).map_or("", |t| t.as_str());
+            open_new_window(app_handle, vod_url(m.as_str(), timestamp));
+            return Ok(());
+        }
+    }
+
+    if let Some(caps) = TWITCH_CLIP_URL_REG.captures(url) {
+        if let Some(m) = caps.get(1) {
+            let url = format!("/streams/clip?slug={}", m.as_str());
+            open_new_window(app_handle, url);
+            return Ok(());
+        }
+    }
+
     if let Some(caps) = TWITCH_URL_REG.captures(url) {
         if let Some(m) = caps.get(1) {
             let username = m.as_str();
@@ -62,6 +106,27 @@ pub fn open_url(app_handle: AppHandle, urls: &[String]) -> Result<()> {
     Err(anyhow!("No matching URL found for '{url}'"))
 }
 
+/// Route of the VOD player, `timestamp` is Twitch's `t` parameter, like `1h2m3s`, converted to seconds.
+fn vod_url(video_id: &str, timestamp: &str) -> String {
+    let mut seconds = 0;
+    let mut number = 0;
+
+    for c in timestamp.chars() {
+        match c {
+            '0'..='9' => number = number * 10 + c.to_digit(10).unwrap_or_default() as u64,
+            'h' => seconds += std::mem::take(&mut number) * 3600,
+            'm' => seconds += std::mem::take(&mut number) * 60,
+            's' => seconds += std::mem::take(&mut number),
+            _ => {}
+        }
+    }
+
+    // Plain seconds, without a unit
+    seconds += number;
+
+    format!("/streams/vod?id={video_id}&t={seconds}")
+}
+
 #[tauri::command]
 pub fn open_new_window(app_handle: AppHandle, url: String) {
     info!("Opening new window for '{url}'");
@@ -12,7 +12,7 @@ pub async fn fetch_channel_by_name(channel_name: &str) -> Result<User> {
 
     let url_target = client.query().resolve_string(channel_name, false).await?;
     let url = url_target.to_url();
-    let channel_id = url.split('/').last().unwrap();
+    let channel_id = url.split('/').next_back().unwrap();
 
     let channel = client.query().channel_videos(channel_id).await?;
 
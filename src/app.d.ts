@@ -14,6 +14,112 @@ declare global {
 	type LiveNow = {
 		username: string;
 		started_at: string;
+		title: string;
+		game: string | null;
+		viewers: number;
+		tags: string[];
+		thumbnail: string;
+	};
+
+	type WentLive = {
+		username: string;
+		title: string;
+		game: string | null;
+	};
+
+	type ChannelProfile = {
+		username: string;
+		displayName: string;
+		description: string;
+		avatar: string;
+		banner: string | null;
+		followers: number;
+		socials: { name: string; title: string; url: string }[];
+		panels: {
+			id: string;
+			title: string | null;
+			description: string | null;
+			image: string | null;
+			link: string | null;
+		}[];
+		schedule: {
+			id: string;
+			startAt: string;
+			endAt: string | null;
+			title: string | null;
+			categories: string[];
+			cancelled: boolean;
+		}[];
+		videos: {
+			id: string;
+			title: string;
+			createdAt: string;
+			duration: number;
+			views: number;
+			thumbnail: string;
+		}[];
+		clips: {
+			slug: string;
+			title: string;
+			createdAt: string;
+			duration: number;
+			views: number;
+			thumbnail: string;
+		}[];
+		// Parts of the profile that failed to load, the rest is still returned
+		errors: string[];
+	};
+
+	type ImportProgress = {
+		username: string;
+		processed: number;
+		total: number;
+		imported: number;
+		skipped: number;
+		failed: number;
+	};
+
+	type SearchChannel = {
+		username: string;
+		displayName: string;
+		avatar: string;
+		followers: number;
+		viewers: number | null;
+		added: boolean;
+	};
+
+	type StreamSession = {
+		id: number;
+		username: string;
+		startedAt: number;
+		lastSeenAt: number;
+		endedAt: number | null;
+		changes: StreamSessionChange[];
+		vodId: string | null;
+	};
+
+	type Category = {
+		id: string;
+		name: string;
+		displayName: string;
+		viewers: number;
+		boxArt: string;
+	};
+
+	type CategoryPage = {
+		categories: Category[];
+		cursor: string | null;
+	};
+
+	type StreamPage = {
+		streams: LiveNow[];
+		cursor: string | null;
+	};
+
+	type StreamSessionChange = {
+		changedAt: number;
+		title: string | null;
+		game: string | null;
 	};
 
 	type YouTubeVideo = {
@@ -24,6 +130,33 @@ declare global {
 		view_count: string;
 	};
 
+	type StreamEvent =
+		| { event: 'adBreakStarted'; data: { username: string; startedAt: number } }
+		| { event: 'adBreakEnded'; data: AdBreak };
+
+	type AdBreak = {
+		username: string;
+		startedAt: number;
+		endedAt: number;
+		duration: number;
+		backupSucceeded: boolean;
+	};
+
+	type Clip = {
+		slug: string;
+		title: string;
+		username: string | null;
+		duration: number;
+		// Best quality first
+		qualities: ClipQuality[];
+	};
+
+	type ClipQuality = {
+		quality: string;
+		frameRate: number;
+		url: string;
+	};
+
 	type ChatEvent = {
 		event: 'message';
 		data: ChatMessage;